@@ -1,6 +1,69 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+mod adjustments;
+mod api;
+mod audit;
+mod auth;
+mod calendar;
+mod cli;
+mod clock;
+mod connector;
+mod error;
+mod fees;
+mod fx;
+mod history;
+mod holds;
+mod idempotency;
+mod journal;
+mod kyc;
+mod ledger;
+mod lifecycle;
+mod money;
+mod nuban;
+mod payouts;
+mod savings;
+mod service;
+mod standing_orders;
+mod statement;
+mod storage;
+#[cfg(test)]
+mod tests;
+
+use adjustments::{Adjustments, Dispute, DisputeState};
+use audit::{AuditLog, AuditReport};
+use auth::{PinCheck, PinCredential, PinPolicy, Pins};
+use calendar::{Date, SECONDS_PER_DAY};
+use clock::{Clock, ManualClock, SystemClock};
+use connector::{BankConnector, ConnectorError, SettlementStatus, new_reference};
+use error::WalletError;
+use fees::{FeePreview, FeeSchedules};
+use fx::{Amount, Currency, FxQuote, RateProvider, RateTable};
+use history::History;
+use holds::{Hold, HoldStatus, Holds};
+use idempotency::{IdempotencyKey, IdempotencyKeys, Outcome, fingerprint};
+use journal::{Journal, JournalEntry, LedgerAccount, Posting, TrialBalance};
+use kyc::{KycTier, LimitKind};
+use ledger::{HistoryQuery, Ledger, Page, Transaction, TransactionKind};
+use lifecycle::{AccountState, StateChange};
+use money::Money;
+use nuban::{AccountNumber, NubanError};
+use payouts::{
+    PaidRows, PayoutBatch, PayoutError, PayoutLine, PayoutOutcome, PayoutPlan, PayoutReport,
+    PayoutResult, PayoutRow, RowError,
+};
+use savings::{Savings, SavingsProduct};
+use standing_orders::{
+    Frequency, OrderStatus, RunOutcome, StandingOrder, StandingOrderRun, StandingOrders,
+};
+use statement::Statement;
+use storage::{Record, Storage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 enum Bank {
     Opay,
     PalmPay,
@@ -8,47 +71,151 @@ enum Bank {
     Moniepoint,
 }
 
+/// What a successful operation hands back: the ledger line it produced for
+/// the account the caller acted on.
+#[derive(Debug, Clone)]
+struct Receipt {
+    transaction_id: u64,
+    account_number: AccountNumber,
+    kind: TransactionKind,
+    amount: Money,
+    fee: Money,
+    balance_after: Money,
+    timestamp: u64,
+}
+
+/// Why a transfer is being made, which decides what its record carries.
 #[derive(Debug)]
-enum Status {
-    Success,
-    InsufficientFunds,
-    AccountNotFound,
+enum Origin {
+    /// The payer asked for it, under the idempotency key they sent, if any.
+    Customer(Option<IdempotencyKey>),
+    StandingOrder(u64),
+    Payout(PayoutLine),
+}
+
+/// A result `idempotent` can hand back again when a call is retried.
+trait Replay: Sized {
+    fn replay(wallet: &Wallet, outcome: Outcome) -> Option<Self>;
+}
+
+/// `ledger` is what the books say an account holds; `available` is what can
+/// still be spent once live holds are set aside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Balance {
+    ledger: Money,
+    available: Money,
 }
 
 #[derive(Debug, Clone)]
 struct User {
     name: String,
     bank: Bank,
-    account_number: u32,
-    balance: u64,
+    account_number: AccountNumber,
+    tier: KycTier,
+    state: AccountState,
+    opened_at: u64,
+    savings: Option<Savings>,
 }
 
-#[derive(Debug, Default)]
+/// Balances are not stored on `User`; they are derived from the postings in
+/// `journal`, and `ledger` keeps the customer-facing history of each change.
+///
+/// Every change goes through `commit`, which writes a `Record` to `storage`
+/// (when the wallet is backed by a file) before applying it in memory.
+#[derive(Debug)]
 struct Wallet {
-    wallet_details: HashMap<u32, User>,
+    wallet_details: HashMap<AccountNumber, User>,
+    journal: Journal,
+    ledger: Ledger,
+    fees: FeeSchedules,
+    connectors: HashMap<Bank, Box<dyn BankConnector>>,
+    rates: Box<dyn RateProvider>,
+    idempotency_keys: IdempotencyKeys,
+    paid_rows: PaidRows,
+    holds: Holds,
+    adjustments: Adjustments,
+    pins: Pins,
+    pin_policy: PinPolicy,
+    state_changes: Vec<StateChange>,
+    dormant_after: Duration,
+    last_end_of_day: Option<Date>,
+    standing_orders: StandingOrders,
+    clock: Arc<dyn Clock>,
+    audit: AuditLog,
+    unaudited: Option<Record>,
+    history: History,
+    storage: Option<Storage>,
 }
 
 impl User {
-    fn new(name: String, bank: Bank, account_number: u32, balance: u64) -> Self {
+    fn new(name: String, bank: Bank, account_number: AccountNumber) -> Self {
         Self {
             name,
             bank,
             account_number,
-            balance,
+            tier: KycTier::default(),
+            state: AccountState::default(),
+            opened_at: 0,
+            savings: None,
         }
     }
+}
 
-    fn deposit(&mut self, amount: u64) -> u64 {
-        self.balance += amount;
-        self.balance
+impl From<&Transaction> for Receipt {
+    fn from(transaction: &Transaction) -> Self {
+        Self {
+            transaction_id: transaction.id,
+            account_number: transaction.account_number,
+            kind: transaction.kind,
+            amount: transaction.amount,
+            fee: transaction.fee,
+            balance_after: transaction.balance_after,
+            timestamp: transaction.timestamp,
+        }
     }
+}
 
-    fn withdraw(&mut self, amount: u64) -> Status {
-        if self.balance < amount {
-            Status::InsufficientFunds
-        } else {
-            self.balance -= amount;
-            Status::Success
+impl Replay for Receipt {
+    fn replay(wallet: &Wallet, outcome: Outcome) -> Option<Self> {
+        match outcome {
+            Outcome::Transaction(id) => wallet.ledger.get(id).map(Receipt::from),
+            _ => None,
+        }
+    }
+}
+
+impl Replay for Hold {
+    fn replay(wallet: &Wallet, outcome: Outcome) -> Option<Self> {
+        match outcome {
+            Outcome::Hold(id) => wallet.holds.get(id).cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl Replay for StandingOrder {
+    fn replay(wallet: &Wallet, outcome: Outcome) -> Option<Self> {
+        match outcome {
+            Outcome::StandingOrder(id) => wallet.standing_orders.get(id).cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl Replay for FxQuote {
+    fn replay(_wallet: &Wallet, outcome: Outcome) -> Option<Self> {
+        match outcome {
+            Outcome::Conversion(quote) => Some(quote),
+            _ => None,
+        }
+    }
+}
+
+impl Replay for Amount {
+    fn replay(_wallet: &Wallet, outcome: Outcome) -> Option<Self> {
+        match outcome {
+            Outcome::Balance(balance) => Some(balance),
+            _ => None,
         }
     }
 }
@@ -57,49 +224,2407 @@ impl Wallet {
     fn new() -> Self {
         Self {
             wallet_details: HashMap::new(),
+            journal: Journal::new(),
+            ledger: Ledger::new(),
+            fees: FeeSchedules::new(),
+            connectors: HashMap::new(),
+            rates: Box::new(RateTable::default()),
+            idempotency_keys: IdempotencyKeys::new(),
+            paid_rows: PaidRows::new(),
+            holds: Holds::new(),
+            adjustments: Adjustments::new(),
+            pins: Pins::new(),
+            pin_policy: PinPolicy::default(),
+            state_changes: Vec::new(),
+            dormant_after: Duration::from_secs(365 * SECONDS_PER_DAY),
+            last_end_of_day: None,
+            standing_orders: StandingOrders::new(),
+            clock: Arc::new(SystemClock),
+            audit: AuditLog::new(),
+            unaudited: None,
+            history: History::new(),
+            storage: None,
+        }
+    }
+
+    /// Loads the wallet saved at `path`, creating an empty one if the file
+    /// does not exist yet. Later changes are appended to the same file.
+    ///
+    /// A last record the audit log never covered is moved out of the log
+    /// into `<log>.unaudited` rather than replayed; see `unaudited`.
+    fn open(path: impl AsRef<Path>) -> Result<Self, WalletError> {
+        let (mut storage, mut records) = Storage::open(path)?;
+        let mut wallet = Self::new();
+        let (audit, unaudited) = AuditLog::open(storage.path(), &records)?;
+        if unaudited.is_some() {
+            audit::set_aside(storage.path(), &records[records.len() - 1])?;
+            storage.drop_last()?;
+            records.pop();
+        }
+        wallet.audit = audit;
+        wallet.unaudited = unaudited;
+        for record in records {
+            wallet.apply(record.clone());
+            wallet.history.push(record);
+        }
+        wallet.storage = Some(storage);
+        Ok(wallet)
+    }
+
+    /// Opens an account at `bank` under the next free NUBAN.
+    fn open_account(&mut self, name: &str, bank: Bank) -> Result<AccountNumber, WalletError> {
+        self.create_account(User::new(
+            name.to_string(),
+            bank,
+            self.next_account_number(bank)?,
+        ))
+    }
+
+    /// Opens an account that earns interest under `product`. A fixed term
+    /// starts counting from today.
+    fn open_savings_account(
+        &mut self,
+        name: &str,
+        bank: Bank,
+        product: SavingsProduct,
+    ) -> Result<AccountNumber, WalletError> {
+        let mut user = User::new(name.to_string(), bank, self.next_account_number(bank)?);
+        user.savings = Some(Savings::new(product, self.now()));
+        self.create_account(user)
+    }
+
+    fn create_account(&mut self, user: User) -> Result<AccountNumber, WalletError> {
+        let account_number = user.account_number;
+        self.add_user(user)?;
+        Ok(account_number)
+    }
+
+    // Serials are shared across banks so a number never names two accounts.
+    fn next_account_number(&self, bank: Bank) -> Result<AccountNumber, WalletError> {
+        let serial = self
+            .wallet_details
+            .keys()
+            .map(|account_number| account_number.serial())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        AccountNumber::new(bank, serial).ok_or(WalletError::Overflow)
+    }
+
+    /// Turns an account number typed by a customer into one of ours. A
+    /// number whose check digit does not match `bank` is refused here, before
+    /// it can reach a transfer.
+    fn resolve_account(&self, input: &str, bank: Bank) -> Result<AccountNumber, WalletError> {
+        let account_number = AccountNumber::parse_for(input, bank)?;
+        match self.wallet_details.get(&account_number) {
+            Some(user) if user.bank == bank => Ok(account_number),
+            _ => Err(WalletError::AccountNotFound(account_number)),
         }
     }
 
-    fn add_user(&mut self, user: User) {
-        self.wallet_details.insert(user.account_number, user);
+    fn accounts(&self) -> Vec<&User> {
+        let mut users: Vec<&User> = self.wallet_details.values().collect();
+        users.sort_by_key(|user| user.account_number);
+        users
     }
 
-    fn deposit_to(&mut self, account_number: u32, amount: u64) -> Status {
-        match self.wallet_details.get_mut(&account_number) {
-            Some(user) => {
-                user.deposit(amount);
-                Status::Success
+    fn add_user(&mut self, user: User) -> Result<(), WalletError> {
+        if !user.account_number.is_valid_for(user.bank) {
+            return Err(NubanError::CheckDigit(user.account_number, user.bank).into());
+        }
+        if self.wallet_details.contains_key(&user.account_number) {
+            return Err(WalletError::DuplicateAccount(user.account_number));
+        }
+        self.commit(Record::AccountAdded {
+            name: user.name,
+            bank: user.bank,
+            account_number: user.account_number,
+            savings: user.savings.map(|savings| savings.product),
+            at: self.now(),
+        })
+    }
+
+    /// Stops all debits on the account, e.g. during an investigation.
+    fn freeze(&mut self, account_number: AccountNumber, reason: &str) -> Result<(), WalletError> {
+        self.change_state(account_number, AccountState::Frozen, reason)
+    }
+
+    fn unfreeze(&mut self, account_number: AccountNumber, reason: &str) -> Result<(), WalletError> {
+        self.activate_from(account_number, AccountState::Frozen, reason)
+    }
+
+    /// Brings a dormant account back into use. An active account that has
+    /// gone idle but not been swept yet is recorded as dormant first, so its
+    /// history shows both steps.
+    fn reactivate(
+        &mut self,
+        account_number: AccountNumber,
+        reason: &str,
+    ) -> Result<(), WalletError> {
+        let user = self.user(account_number)?;
+        if user.state == AccountState::Active && self.is_idle(user, self.now()) {
+            self.change_state(account_number, AccountState::Dormant, "no activity")?;
+        }
+        self.activate_from(account_number, AccountState::Dormant, reason)
+    }
+
+    // Unfreezing and reactivating both end in `Active`, but each only from
+    // its own state: reactivating must not lift a freeze.
+    fn activate_from(
+        &mut self,
+        account_number: AccountNumber,
+        from: AccountState,
+        reason: &str,
+    ) -> Result<(), WalletError> {
+        let state = self.user(account_number)?.state;
+        if state != from {
+            return Err(WalletError::InvalidStateChange {
+                account: account_number,
+                from: state,
+                to: AccountState::Active,
+            });
+        }
+        self.change_state(account_number, AccountState::Active, reason)
+    }
+
+    /// How long an account can go without a transaction before it is dormant.
+    fn set_dormant_after(&mut self, dormant_after: Duration) {
+        self.dormant_after = dormant_after;
+    }
+
+    /// Records every active account that has been idle past the dormancy
+    /// period as dormant. Debits on such accounts are refused even before
+    /// this runs; the sweep makes the change visible and audited.
+    fn mark_dormant_accounts(&mut self) -> Result<Vec<AccountNumber>, WalletError> {
+        let at = self.now();
+        let mut idle: Vec<AccountNumber> = self
+            .wallet_details
+            .values()
+            .filter(|user| user.state == AccountState::Active && self.is_idle(user, at))
+            .map(|user| user.account_number)
+            .collect();
+        idle.sort_unstable();
+        for &account_number in &idle {
+            self.change_state(account_number, AccountState::Dormant, "no activity")?;
+        }
+        Ok(idle)
+    }
+
+    /// Closes the account for good. Whatever balance is left is first swept
+    /// to `sweep_to`; without a sweep account the balance must already be zero.
+    fn close_account(
+        &mut self,
+        account_number: AccountNumber,
+        sweep_to: Option<AccountNumber>,
+        reason: &str,
+    ) -> Result<(), WalletError> {
+        let state = self.user(account_number)?.state;
+        if !state.can_move_to(AccountState::Closed) {
+            return Err(WalletError::InvalidStateChange {
+                account: account_number,
+                from: state,
+                to: AccountState::Closed,
+            });
+        }
+        if !self.holds.held_for(account_number, self.now()).is_zero() {
+            return Err(WalletError::HoldsOutstanding(account_number));
+        }
+
+        let balance = self.journal.customer_balance(account_number);
+        if !balance.is_zero() {
+            let Some(sweep_to) = sweep_to else {
+                return Err(WalletError::BalanceNotZero {
+                    account: account_number,
+                    balance,
+                });
+            };
+            if sweep_to == account_number {
+                return Err(WalletError::SameAccount(account_number));
             }
-            None => Status::AccountNotFound,
+            self.ensure_can_receive(sweep_to, balance)?;
+            self.commit(Record::Transferred {
+                from: account_number,
+                to: sweep_to,
+                amount: balance,
+                fee: Money::ZERO,
+                standing_order: None,
+                narration: None,
+                settlement: None,
+                payout: None,
+                key: None,
+                at: self.now(),
+            })?;
+        }
+        self.change_state(account_number, AccountState::Closed, reason)
+    }
+
+    /// Every state change on the account, oldest first.
+    fn state_history(&self, account_number: AccountNumber) -> Vec<&StateChange> {
+        self.state_changes
+            .iter()
+            .filter(|change| change.account_number == account_number)
+            .collect()
+    }
+
+    fn change_state(
+        &mut self,
+        account_number: AccountNumber,
+        to: AccountState,
+        reason: &str,
+    ) -> Result<(), WalletError> {
+        let from = self.user(account_number)?.state;
+        if !from.can_move_to(to) {
+            return Err(WalletError::InvalidStateChange {
+                account: account_number,
+                from,
+                to,
+            });
+        }
+        self.commit(Record::AccountStateChanged {
+            account_number,
+            state: to,
+            reason: reason.to_string(),
+            at: self.now(),
+        })
+    }
+
+    /// Idle since the later of its last transaction and the last time it
+    /// was made active, so a reactivated or unfrozen account starts afresh.
+    fn is_idle(&self, user: &User, at: u64) -> bool {
+        let last_transaction = self
+            .ledger
+            .latest_for(user.account_number)
+            .map(|t| t.timestamp);
+        let last_activated = self
+            .state_changes
+            .iter()
+            .rev()
+            .find(|change| {
+                change.account_number == user.account_number && change.to == AccountState::Active
+            })
+            .map(|change| change.at);
+        let last_activity = [last_transaction, last_activated]
+            .into_iter()
+            .flatten()
+            .fold(user.opened_at, u64::max);
+        at.saturating_sub(last_activity) >= self.dormant_after.as_secs()
+    }
+
+    /// Debits need an active account that has not gone idle.
+    fn ensure_can_debit(&self, account_number: AccountNumber) -> Result<(), WalletError> {
+        let user = self.user(account_number)?;
+        let state = match user.state {
+            AccountState::Active if self.is_idle(user, self.now()) => AccountState::Dormant,
+            state => state,
+        };
+        if !state.can_debit() {
+            return Err(WalletError::AccountNotActive {
+                account: account_number,
+                state,
+            });
         }
+        Ok(())
     }
 
-    fn withdraw_from(&mut self, account_number: u32, amount: u64) -> Status {
-        match self.wallet_details.get_mut(&account_number) {
-            Some(user) => user.withdraw(amount),
-            None => Status::AccountNotFound,
+    fn ensure_can_credit(&self, account_number: AccountNumber) -> Result<(), WalletError> {
+        let state = self.user(account_number)?.state;
+        if !state.can_credit() {
+            return Err(WalletError::AccountNotActive {
+                account: account_number,
+                state,
+            });
         }
+        Ok(())
     }
 
-    fn balance_of(&self, account_number: u32) -> Option<u64> {
-        self.wallet_details.get(&account_number).map(|user| user.balance)
+    /// `key`, when given, makes a retried deposit return the original
+    /// receipt instead of paying in twice; see `idempotent`.
+    fn deposit_to(
+        &mut self,
+        account_number: AccountNumber,
+        amount: Money,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint("deposit", &[account_number], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.ensure_account(account_number)?;
+            wallet.ensure_can_receive(account_number, amount)?;
+
+            wallet.commit(Record::Deposited {
+                account_number,
+                amount,
+                key,
+                at: wallet.now(),
+            })?;
+            Ok(wallet.receipt(account_number))
+        })
+    }
+
+    /// Credits money received in any currency and returns the new balance
+    /// in it. Naira goes through `deposit_to`; other currencies are held as a
+    /// separate balance.
+    fn deposit_currency(
+        &mut self,
+        account_number: AccountNumber,
+        amount: Amount,
+        key: Option<&str>,
+    ) -> Result<Amount, WalletError> {
+        ensure_positive(amount.value)?;
+        if amount.currency == Currency::Ngn {
+            self.deposit_to(account_number, amount.value, key)?;
+            return self.balance_in(account_number, amount.currency);
+        }
+        let operation = format!("deposit {}", amount.currency);
+        let request = fingerprint(&operation, &[account_number], amount.value);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.ensure_can_credit(account_number)?;
+            wallet
+                .balance_in(account_number, amount.currency)?
+                .value
+                .checked_add(amount.value)
+                .ok_or(WalletError::Overflow)?;
+            wallet.commit(Record::CurrencyDeposited {
+                account_number,
+                currency: amount.currency,
+                amount: amount.value,
+                key,
+                at: wallet.now(),
+            })?;
+            wallet.balance_in(account_number, amount.currency)
+        })
+    }
+
+    /// Converts `amount` of the account's `from` balance into `to` at the
+    /// current rates, keeping the spread as FX income.
+    fn convert(
+        &mut self,
+        account_number: AccountNumber,
+        from: Currency,
+        to: Currency,
+        amount: Money,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<FxQuote, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint(&format!("convert {from} {to}"), &[account_number], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.ensure_account(account_number)?;
+            let quote = wallet.quote_conversion(from, to, amount)?;
+            wallet.verify_pin(account_number, pin)?;
+            if from == Currency::Ngn {
+                // Selling naira moves it out of the account, so the tier's
+                // single and daily limits apply as they do to a transfer.
+                wallet.ensure_can_send(account_number, amount)?;
+                wallet.ensure_funds(account_number, amount)?;
+            } else {
+                wallet.ensure_can_debit(account_number)?;
+                let available = wallet.balance_in(account_number, from)?;
+                if available.value < amount {
+                    return Err(WalletError::InsufficientCurrency {
+                        account: account_number,
+                        needed: quote.sold,
+                        available,
+                    });
+                }
+            }
+            if to == Currency::Ngn {
+                wallet.ensure_can_receive(account_number, quote.bought.value)?;
+            } else {
+                wallet.ensure_can_credit(account_number)?;
+                wallet
+                    .balance_in(account_number, to)?
+                    .value
+                    .checked_add(quote.bought.value)
+                    .ok_or(WalletError::Overflow)?;
+            }
+
+            wallet.commit(Record::Converted {
+                account_number,
+                from,
+                to,
+                sold: amount,
+                bought: quote.bought.value,
+                mid: quote.mid.value,
+                key,
+                at: wallet.now(),
+            })?;
+            Ok(quote)
+        })
+    }
+
+    fn quote_conversion(
+        &self,
+        from: Currency,
+        to: Currency,
+        amount: Money,
+    ) -> Result<FxQuote, WalletError> {
+        Ok(fx::quote(self.rates.as_ref(), from, to, amount)?)
     }
-}
 
-fn main() {
-    let mut wallet = Wallet::new();
+    /// Replaces the rates with those in the file at `path`. Conversions
+    /// already made keep the rates they were made at.
+    fn load_rates(&mut self, path: impl AsRef<Path>) -> Result<(), WalletError> {
+        self.set_rate_provider(Box::new(RateTable::load(path)?));
+        Ok(())
+    }
 
-    let user1 = User::new("Uche".to_string(), Bank::Kuda, 1001, 5_000);
-    let user2 = User::new("Ada".to_string(), Bank::Opay, 1002, 8_500);
+    fn set_rate_provider(&mut self, rates: Box<dyn RateProvider>) {
+        self.rates = rates;
+    }
 
-    wallet.add_user(user1);
-    wallet.add_user(user2);
+    fn balance_in(
+        &self,
+        account_number: AccountNumber,
+        currency: Currency,
+    ) -> Result<Amount, WalletError> {
+        self.ensure_account(account_number)?;
+        let held = -self.journal.net_debit(holding(account_number, currency));
+        let held = u64::try_from(held).map_or(Money::ZERO, Money::from_kobo);
+        Ok(Amount::new(currency, held))
+    }
 
-    let deposit_status = wallet.deposit_to(1001, 4_000);
-    let withdraw_status = wallet.withdraw_from(1002, 7_000);
+    /// The naira balance and every other currency the account holds.
+    fn balances(&self, account_number: AccountNumber) -> Result<Vec<Amount>, WalletError> {
+        let mut balances = Vec::new();
+        for currency in Currency::ALL {
+            let balance = self.balance_in(account_number, currency)?;
+            if currency == Currency::Ngn || !balance.value.is_zero() {
+                balances.push(balance);
+            }
+        }
+        Ok(balances)
+    }
+
+    fn withdraw_from(
+        &mut self,
+        account_number: AccountNumber,
+        amount: Money,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint("withdrawal", &[account_number], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.ensure_account(account_number)?;
+            wallet.verify_pin(account_number, pin)?;
+            wallet.ensure_can_send(account_number, amount)?;
+            wallet.ensure_funds(account_number, amount)?;
+
+            wallet.commit(Record::Withdrawn {
+                account_number,
+                amount,
+                key,
+                at: wallet.now(),
+            })?;
+            Ok(wallet.receipt(account_number))
+        })
+    }
+
+    /// Returns the sender's receipt. Transfers to another bank are charged
+    /// the sender's bank fee on top of `amount`.
+    fn transfer(
+        &mut self,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint("transfer", &[from, to], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.preview_transfer_fee(from, to, amount)?;
+            wallet.verify_pin(from, pin)?;
+            wallet.send(from, to, amount, None, Origin::Customer(key))
+        })
+    }
+
+    /// The checks and settlement behind `transfer`, once the payer has been
+    /// authorised. `narration` shows on both sides' history.
+    fn send(
+        &mut self,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        narration: Option<&str>,
+        origin: Origin,
+    ) -> Result<Receipt, WalletError> {
+        let preview = self.preview_transfer_fee(from, to, amount)?;
+        self.ensure_can_send(from, amount)?;
+        self.ensure_funds(from, preview.total_debit)?;
+        self.ensure_can_receive(to, amount)?;
+
+        let at = self.now();
+        let receiver_bank = self.user(to)?.bank;
+        let settlement = if preview.inter_bank && self.connectors.contains_key(&receiver_bank) {
+            let reference = new_reference(&format!("TRF-{from}-{to}"))
+                .map_err(|err| WalletError::Random(err.to_string()))?;
+            Some(reference)
+        } else {
+            None
+        };
+        let (standing_order, payout, key) = match origin {
+            Origin::Customer(key) => (None, None, key),
+            Origin::StandingOrder(order_id) => (Some(order_id), None, None),
+            Origin::Payout(payout) => (None, Some(payout), None),
+        };
+        let record = Record::Transferred {
+            from,
+            to,
+            amount,
+            fee: preview.fee,
+            standing_order,
+            narration: narration.map(str::to_string),
+            settlement: settlement.clone(),
+            payout,
+            key,
+            at,
+        };
+        let Some(reference) = settlement else {
+            self.commit(record)?;
+            return Ok(self.receipt(from));
+        };
+
+        self.settle_with(receiver_bank, &reference, to, amount)?;
+        if let Err(err) = self.commit(record) {
+            // The other bank has the money but we could not record it; pull it
+            // back so neither side moves.
+            if let Ok(connector) = self.connector(receiver_bank) {
+                let _ = connector.reverse(&reference);
+            }
+            return Err(err);
+        }
+        Ok(self.receipt(from))
+    }
+
+    /// Checks a payout batch without moving money. Every row must name an
+    /// account other than the funding one that can take its amount, counting
+    /// other rows to the same account; every bad row is reported, not just
+    /// the first. Then the funding account must cover the whole batch, fees
+    /// included, inside its limits. Rows already paid under the batch's id
+    /// are left out.
+    fn validate_payouts(&self, batch: &PayoutBatch) -> Result<PayoutPlan, WalletError> {
+        if batch.rows.is_empty() {
+            return Err(PayoutError::Empty(batch.id.clone()).into());
+        }
+        let funding = batch.funding;
+        self.ensure_can_debit(funding)?;
+        let sender = self.user(funding)?;
+        let limits = sender.tier.limits();
+        let schedule = self.fees.for_bank(sender.bank);
+        let mut inter_bank_used = self.inter_bank_transfers_this_month(funding, self.now());
+        let mut incoming: HashMap<AccountNumber, Money> = HashMap::new();
+        let mut plan = PayoutPlan {
+            rows: 0,
+            amount: Money::ZERO,
+            fees: Money::ZERO,
+            total_debit: Money::ZERO,
+        };
+        let mut errors = Vec::new();
+
+        for row in &batch.rows {
+            match self.payout_paid(batch, row) {
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(err) => {
+                    errors.push(RowError {
+                        line: row.line,
+                        reason: err.to_string(),
+                    });
+                    continue;
+                }
+            }
+            let already = incoming
+                .get(&row.account_number)
+                .copied()
+                .unwrap_or(Money::ZERO);
+            let checked = self
+                .check_payout_row(funding, row, already)
+                .and_then(|inter_bank| {
+                    if !inter_bank {
+                        return Ok(Money::ZERO);
+                    }
+                    let fee = schedule
+                        .fee_for(row.amount, inter_bank_used)
+                        .ok_or(WalletError::Overflow)?;
+                    inter_bank_used += 1;
+                    Ok(fee)
+                });
+            let fee = match checked {
+                Ok(fee) => fee,
+                Err(err) => {
+                    errors.push(RowError {
+                        line: row.line,
+                        reason: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            incoming.insert(
+                row.account_number,
+                already
+                    .checked_add(row.amount)
+                    .ok_or(WalletError::Overflow)?,
+            );
+            plan.rows += 1;
+            plan.amount = plan
+                .amount
+                .checked_add(row.amount)
+                .ok_or(WalletError::Overflow)?;
+            plan.fees = plan.fees.checked_add(fee).ok_or(WalletError::Overflow)?;
+        }
+        if !errors.is_empty() {
+            return Err(PayoutError::InvalidRows {
+                batch: batch.id.clone(),
+                rows: errors,
+            }
+            .into());
+        }
+        let sent_today = self
+            .sent_today(funding)?
+            .checked_add(plan.amount)
+            .ok_or(WalletError::Overflow)?;
+        if sent_today > limits.daily {
+            return Err(self.limit_exceeded(funding, LimitKind::Daily, sent_today));
+        }
+        plan.total_debit = plan
+            .amount
+            .checked_add(plan.fees)
+            .ok_or(WalletError::Overflow)?;
+        self.ensure_funds(funding, plan.total_debit)?;
+        Ok(plan)
+    }
+
+    // One payout row on its own, with `already` going to the same account
+    // earlier in the batch. Returns whether the row crosses banks.
+    fn check_payout_row(
+        &self,
+        funding: AccountNumber,
+        row: &PayoutRow,
+        already: Money,
+    ) -> Result<bool, WalletError> {
+        let to = row.account_number;
+        if to == funding {
+            return Err(WalletError::SameAccount(funding));
+        }
+        let receiver = self.user(to)?;
+        self.ensure_can_credit(to)?;
+        let sender_limits = self.user(funding)?.tier.limits();
+        if row.amount > sender_limits.single_transaction {
+            return Err(self.limit_exceeded(funding, LimitKind::SingleTransaction, row.amount));
+        }
+        let receiver_limits = receiver.tier.limits();
+        if row.amount > receiver_limits.single_transaction {
+            return Err(self.limit_exceeded(to, LimitKind::SingleTransaction, row.amount));
+        }
+        let balance_after = self
+            .journal
+            .customer_balance(to)
+            .checked_add(already)
+            .and_then(|balance| balance.checked_add(row.amount))
+            .ok_or(WalletError::Overflow)?;
+        if balance_after > receiver_limits.max_balance {
+            return Err(self.limit_exceeded(to, LimitKind::MaxBalance, balance_after));
+        }
+        Ok(receiver.bank != self.user(funding)?.bank)
+    }
+
+    /// Validates the whole batch, then pays it row by row out of the funding
+    /// account, authorised once with its PIN. A row that fails now, say on a
+    /// switch timeout, does not stop the rest; the report's `retry_batch`
+    /// holds the rows to run again. Each payment records its batch id, line
+    /// and row fingerprint, so no row is ever paid twice.
+    fn run_payouts(&mut self, batch: &PayoutBatch, pin: &str) -> Result<PayoutReport, WalletError> {
+        self.validate_payouts(batch)?;
+        self.verify_pin(batch.funding, pin)?;
+
+        let mut results = Vec::with_capacity(batch.rows.len());
+        for row in &batch.rows {
+            let narration = Some(row.narration.as_str()).filter(|narration| !narration.is_empty());
+            let paid = self.payout_paid(batch, row).and_then(|paid| match paid {
+                Some(transaction_id) => self
+                    .ledger
+                    .get(transaction_id)
+                    .map(Receipt::from)
+                    .ok_or(WalletError::TransactionNotFound(transaction_id)),
+                None => self.send(
+                    batch.funding,
+                    row.account_number,
+                    row.amount,
+                    narration,
+                    Origin::Payout(row.paid_as(&batch.id)),
+                ),
+            });
+            let outcome = match paid {
+                Ok(receipt) => PayoutOutcome::Paid(receipt),
+                Err(err) => PayoutOutcome::Failed(err),
+            };
+            results.push(PayoutResult {
+                row: row.clone(),
+                outcome,
+            });
+        }
+        Ok(PayoutReport {
+            batch: batch.id.clone(),
+            funding: batch.funding,
+            results,
+        })
+    }
+
+    // The transfer that already paid `row`, if any. A row whose line was
+    // paid with a different account, amount or narration is refused.
+    fn payout_paid(
+        &self,
+        batch: &PayoutBatch,
+        row: &PayoutRow,
+    ) -> Result<Option<u64>, WalletError> {
+        let Some(paid) = self.paid_rows.get(batch.funding, &batch.id, row.line) else {
+            return Ok(None);
+        };
+        if paid.fingerprint != row.fingerprint() {
+            return Err(PayoutError::RowChanged(paid.transaction_id).into());
+        }
+        Ok(Some(paid.transaction_id))
+    }
+
+    /// Sets up a standing order, authorised once with the payer's PIN. The
+    /// first payment is due on the first matching date from today.
+    fn add_standing_order(
+        &mut self,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        frequency: Frequency,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<StandingOrder, WalletError> {
+        ensure_positive(amount)?;
+        let operation = format!("standing order {frequency:?}");
+        let request = fingerprint(&operation, &[from, to], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.preview_transfer_fee(from, to, amount)?;
+            wallet.ensure_can_debit(from)?;
+            wallet.ensure_can_credit(to)?;
+            wallet.verify_pin(from, pin)?;
+
+            let order_id = wallet.standing_orders.next_id();
+            let at = wallet.now();
+            let first_due = frequency.first_on_or_after(Date::from_timestamp(at));
+            wallet.commit(Record::StandingOrderCreated {
+                order_id,
+                from,
+                to,
+                amount,
+                frequency,
+                first_due: first_due.timestamp(),
+                key,
+                at,
+            })?;
+            wallet.standing_order(order_id).cloned()
+        })
+    }
+
+    fn cancel_standing_order(&mut self, order_id: u64) -> Result<StandingOrder, WalletError> {
+        if self.standing_order(order_id)?.status != OrderStatus::Active {
+            return Err(WalletError::StandingOrderNotActive(order_id));
+        }
+        self.commit(Record::StandingOrderCancelled {
+            order_id,
+            at: self.now(),
+        })?;
+        self.standing_order(order_id).cloned()
+    }
+
+    fn standing_order(&self, order_id: u64) -> Result<&StandingOrder, WalletError> {
+        self.standing_orders
+            .get(order_id)
+            .ok_or(WalletError::StandingOrderNotFound(order_id))
+    }
+
+    fn standing_orders_for(&self, account_number: AccountNumber) -> Vec<&StandingOrder> {
+        self.standing_orders.for_account(account_number)
+    }
+
+    /// How long a payment that failed for lack of funds keeps being retried
+    /// before it is missed. Defaults to three days from the due date.
+    fn set_standing_order_retry_window(&mut self, retry_window: Duration) {
+        self.standing_orders.set_retry_window(retry_window);
+    }
+
+    /// Makes every standing order payment that is due, one per order per
+    /// run. A payment short of funds stays due and is retried on later runs
+    /// until its retry window closes; any other failure, or running out of
+    /// window, misses that payment and moves the order on to its next date.
+    fn run_standing_orders(&mut self) -> Result<Vec<StandingOrderRun>, WalletError> {
+        let at = self.now();
+        let retry_window = self.standing_orders.retry_window().as_secs();
+        let mut runs = Vec::new();
+        for order_id in self.standing_orders.due(at) {
+            let order = self.standing_order(order_id)?.clone();
+            let retry_until = order.next_due.timestamp() + retry_window;
+            let outcome = match self.send(
+                order.from,
+                order.to,
+                order.amount,
+                None,
+                Origin::StandingOrder(order_id),
+            ) {
+                Ok(receipt) => RunOutcome::Paid(receipt),
+                Err(reason @ WalletError::InsufficientFunds { .. }) if at < retry_until => {
+                    RunOutcome::Retrying {
+                        until: retry_until,
+                        reason,
+                    }
+                }
+                Err(WalletError::Storage(err)) => return Err(WalletError::Storage(err)),
+                Err(reason) => {
+                    self.commit(Record::StandingOrderMissed {
+                        order_id,
+                        reason: reason.to_string(),
+                        at,
+                    })?;
+                    RunOutcome::Missed(reason)
+                }
+            };
+            runs.push(StandingOrderRun {
+                order_id,
+                due: order.next_due,
+                outcome,
+            });
+        }
+        Ok(runs)
+    }
+
+    /// The wallet as it stood at `at`, rebuilt by replaying every record up
+    /// to then. It is a read-only view: it has no log, and its clock is
+    /// stopped at `at`.
+    fn as_of(&self, at: u64) -> Wallet {
+        let mut past = Wallet::new();
+        past.set_clock(Arc::new(ManualClock::new(at)));
+        for record in self.history.replay_to(at) {
+            past.apply(record.clone());
+        }
+        past
+    }
+
+    /// The account's balance at the close of `date`, UTC.
+    fn balance_on(&self, account_number: AccountNumber, date: Date) -> Option<Balance> {
+        self.as_of(date.next_day().timestamp() - 1)
+            .balance_of(account_number)
+    }
+
+    /// Hash of the newest audit entry. Publishing it somewhere the wallet
+    /// cannot write lets `verify_audit` later prove nothing was cut off the
+    /// end of the log.
+    fn audit_head(&self) -> &str {
+        self.audit.head()
+    }
+
+    /// Checks the audit log against itself, against the wallet log, against
+    /// the head this wallet last wrote and, if given, against a head
+    /// published earlier.
+    fn verify_audit(&self, published: Option<&str>) -> Result<AuditReport, WalletError> {
+        let Some(storage) = self.storage.as_ref() else {
+            return Err(WalletError::NotPersistent);
+        };
+        Ok(audit::verify(
+            storage.path(),
+            Some(self.audit.head()),
+            published,
+        )?)
+    }
+
+    /// The record `open` found past the end of the audit log, if any. It was
+    /// set aside for an operator and is not part of this wallet.
+    fn unaudited(&self) -> Option<&Record> {
+        self.unaudited.as_ref()
+    }
+
+    /// Replaces the wallet's clock, e.g. with a `ManualClock` to step through
+    /// schedules.
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    /// Routes inter-bank transfers into `bank` through `connector` instead of
+    /// settling them only on our own books.
+    fn add_connector(&mut self, connector: Box<dyn BankConnector>) {
+        self.connectors.insert(connector.bank(), connector);
+    }
+
+    /// Runs a mutating call at most once per `key`; without a key it just
+    /// runs. A retry with a key that already succeeded gets the original
+    /// result back and changes nothing, as long as it asks for the same
+    /// thing: a key reused for a request with another `fingerprint` is an
+    /// `IdempotencyConflict`. The operation puts the key on the record it
+    /// commits, so the call and its key are written in one append and a
+    /// crash cannot keep one without the other. Failed calls are not
+    /// remembered: they changed nothing either, so a retry is free to try
+    /// again.
+    fn idempotent<T, F>(
+        &mut self,
+        key: Option<&str>,
+        fingerprint: String,
+        operation: F,
+    ) -> Result<T, WalletError>
+    where
+        T: Replay,
+        F: FnOnce(&mut Self, Option<IdempotencyKey>) -> Result<T, WalletError>,
+    {
+        let Some(key) = key else {
+            return operation(self, None);
+        };
+        let at = self.now();
+        self.idempotency_keys.prune(at);
+        if let Some(result) = self
+            .idempotency_keys
+            .lookup(key, &fingerprint, at)?
+            .and_then(|outcome| T::replay(self, outcome))
+        {
+            return Ok(result);
+        }
+        let key = IdempotencyKey {
+            key: key.to_string(),
+            fingerprint,
+        };
+        operation(self, Some(key))
+    }
+
+    /// How long `idempotent` remembers a key. Defaults to 24 hours.
+    fn set_idempotency_retention(&mut self, retention: Duration) {
+        self.idempotency_keys.set_retention(retention);
+    }
+
+    /// Sets the transaction PIN on an account that does not have one yet.
+    /// Debits on the account need it from then on.
+    fn set_pin(&mut self, account_number: AccountNumber, pin: &str) -> Result<(), WalletError> {
+        self.ensure_account(account_number)?;
+        if self.pins.has_pin(account_number) {
+            return Err(WalletError::PinAlreadySet(account_number));
+        }
+        self.install_pin(account_number, pin)
+    }
+
+    /// Starts a PIN reset and returns a one-time token for the customer to
+    /// receive out of band. Only a hash of the token is kept.
+    fn request_pin_reset(&mut self, account_number: AccountNumber) -> Result<String, WalletError> {
+        self.ensure_account(account_number)?;
+        let token = auth::new_reset_token().map_err(|err| WalletError::Random(err.to_string()))?;
+        let at = self.now();
+        self.commit(Record::PinResetRequested {
+            account_number,
+            token_hash: auth::hash_token(&token),
+            expires_at: at.saturating_add(self.pin_policy.reset_valid_for_secs),
+            at,
+        })?;
+        Ok(token)
+    }
+
+    /// Finishes a reset with the token from `request_pin_reset`. This also
+    /// lifts a lockout.
+    fn complete_pin_reset(
+        &mut self,
+        account_number: AccountNumber,
+        token: &str,
+        new_pin: &str,
+    ) -> Result<(), WalletError> {
+        self.ensure_account(account_number)?;
+        if !self
+            .pins
+            .reset_token_valid(account_number, token, self.now())
+        {
+            return Err(WalletError::InvalidResetToken(account_number));
+        }
+        self.install_pin(account_number, new_pin)
+    }
+
+    fn install_pin(&mut self, account_number: AccountNumber, pin: &str) -> Result<(), WalletError> {
+        if !auth::valid_pin(pin) {
+            return Err(WalletError::InvalidPinFormat);
+        }
+        let credential = PinCredential::new(pin, self.pin_policy.rounds)
+            .map_err(|err| WalletError::Random(err.to_string()))?;
+        self.commit(Record::PinSet {
+            account_number,
+            credential,
+            at: self.now(),
+        })
+    }
+
+    /// Checks the PIN before a debit. Each wrong PIN is recorded, and the
+    /// account locks once `max_attempts` are used up in a row.
+    fn verify_pin(&mut self, account_number: AccountNumber, pin: &str) -> Result<(), WalletError> {
+        match self.pins.check(account_number, pin) {
+            PinCheck::NotSet => Err(WalletError::PinNotSet(account_number)),
+            PinCheck::Locked => Err(WalletError::PinLocked(account_number)),
+            PinCheck::Correct => {
+                if self.pins.failed_attempts(account_number) > 0 {
+                    self.commit(Record::PinVerified {
+                        account_number,
+                        at: self.now(),
+                    })?;
+                }
+                Ok(())
+            }
+            PinCheck::Incorrect { failed_attempts } => {
+                let locked = failed_attempts >= self.pin_policy.max_attempts;
+                self.commit(Record::PinFailed {
+                    account_number,
+                    locked,
+                    at: self.now(),
+                })?;
+                if locked {
+                    Err(WalletError::PinLocked(account_number))
+                } else {
+                    Err(WalletError::IncorrectPin {
+                        account: account_number,
+                        attempts_left: self.pin_policy.max_attempts - failed_attempts,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Moves an account to a higher KYC tier, raising its limits.
+    fn upgrade_tier(
+        &mut self,
+        account_number: AccountNumber,
+        tier: KycTier,
+    ) -> Result<(), WalletError> {
+        let current = self.user(account_number)?.tier;
+        if tier <= current {
+            return Err(WalletError::InvalidTierChange {
+                account: account_number,
+                from: current,
+                to: tier,
+            });
+        }
+        self.commit(Record::TierUpgraded {
+            account_number,
+            tier,
+            at: self.now(),
+        })
+    }
+
+    /// Replaces every bank's fee schedule with those in the file at `path`.
+    /// Fees already charged stay as they were.
+    fn load_fees(&mut self, path: impl AsRef<Path>) -> Result<(), WalletError> {
+        self.fees = FeeSchedules::load(path)?;
+        Ok(())
+    }
+
+    /// What `transfer` would charge right now, without moving any money.
+    fn preview_transfer_fee(
+        &self,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+    ) -> Result<FeePreview, WalletError> {
+        let sender = self.user(from)?;
+        let receiver = self.user(to)?;
+        if from == to {
+            return Err(WalletError::SameAccount(from));
+        }
+
+        let inter_bank = sender.bank != receiver.bank;
+        let (fee, free_transfers_left) = if inter_bank {
+            let schedule = self.fees.for_bank(sender.bank);
+            let used = self.inter_bank_transfers_this_month(from, self.now());
+            let fee = schedule
+                .fee_for(amount, used)
+                .ok_or(WalletError::Overflow)?;
+            (fee, schedule.free_transfers_left(used))
+        } else {
+            (Money::ZERO, 0)
+        };
+
+        Ok(FeePreview {
+            amount,
+            fee,
+            total_debit: amount.checked_add(fee).ok_or(WalletError::Overflow)?,
+            inter_bank,
+            free_transfers_left,
+        })
+    }
+
+    /// Reserves `amount` on the account for up to `expires_in`. The money
+    /// stays on the ledger but can no longer be spent by anything else.
+    fn authorize_hold(
+        &mut self,
+        account_number: AccountNumber,
+        amount: Money,
+        expires_in: Duration,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<Hold, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint("hold", &[account_number], amount);
+        self.idempotent(key, request, |wallet, key| {
+            wallet.ensure_account(account_number)?;
+            wallet.verify_pin(account_number, pin)?;
+            wallet.ensure_can_send(account_number, amount)?;
+            wallet.ensure_funds(account_number, amount)?;
+
+            let at = wallet.now();
+            let hold_id = wallet.holds.next_id();
+            wallet.commit(Record::HoldPlaced {
+                hold_id,
+                account_number,
+                amount,
+                expires_at: at.saturating_add(expires_in.as_secs()),
+                key,
+                at,
+            })?;
+            Ok(wallet.live_hold(hold_id, at)?.clone())
+        })
+    }
+
+    /// Debits up to the held amount. The hold is finished either way; any
+    /// part of it that was not captured goes back to the available balance.
+    fn capture_hold(
+        &mut self,
+        hold_id: u64,
+        amount: Money,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint(&format!("capture {hold_id}"), &[], amount);
+        self.idempotent(key, request, |wallet, key| {
+            let at = wallet.now();
+            let hold = wallet.live_hold(hold_id, at)?;
+            if amount > hold.amount {
+                return Err(WalletError::CaptureExceedsHold {
+                    hold: hold_id,
+                    requested: amount,
+                    held: hold.amount,
+                });
+            }
+            let account_number = hold.account_number;
+            wallet.ensure_can_debit(account_number)?;
+
+            wallet.commit(Record::HoldCaptured {
+                hold_id,
+                amount,
+                key,
+                at,
+            })?;
+            Ok(wallet.receipt(account_number))
+        })
+    }
+
+    fn void_hold(&mut self, hold_id: u64) -> Result<(), WalletError> {
+        let at = self.now();
+        self.live_hold(hold_id, at)?;
+        self.commit(Record::HoldVoided { hold_id, at })
+    }
+
+    /// Marks every hold past its expiry as expired. Lapsed holds already stop
+    /// counting against the available balance; this just records it.
+    fn expire_holds(&mut self) -> Result<usize, WalletError> {
+        let at = self.now();
+        let lapsed = self.holds.lapsed(at);
+        for &hold_id in &lapsed {
+            self.commit(Record::HoldExpired { hold_id, at })?;
+        }
+        Ok(lapsed.len())
+    }
+
+    fn live_hold(&self, hold_id: u64, at: u64) -> Result<&Hold, WalletError> {
+        let hold = self
+            .holds
+            .get(hold_id)
+            .ok_or(WalletError::HoldNotFound(hold_id))?;
+        if !hold.is_live(at) {
+            return Err(WalletError::HoldNotActive(hold_id));
+        }
+        Ok(hold)
+    }
+
+    /// Undoes a transaction in full, fee included. A transfer can be reversed
+    /// through either of its two ledger lines; one settled with another bank
+    /// is reversed at that bank first.
+    fn reverse(&mut self, transaction_id: u64, key: Option<&str>) -> Result<Receipt, WalletError> {
+        let request = fingerprint(&format!("reversal {transaction_id}"), &[], Money::ZERO);
+        self.idempotent(key, request, |wallet, key| {
+            let original = wallet.adjustable(transaction_id)?;
+            if !wallet.adjustments.refunded(original.id).is_zero() {
+                return Err(WalletError::NotReversible(original.id));
+            }
+            match original.kind {
+                TransactionKind::Deposit => {
+                    wallet.ensure_funds(original.account_number, original.amount)?
+                }
+                TransactionKind::Withdrawal | TransactionKind::Capture => {
+                    wallet.ensure_room(original.account_number, original.amount)?
+                }
+                TransactionKind::TransferOut => {
+                    let receiver = original
+                        .counterparty
+                        .expect("transfers have a counterparty");
+                    wallet.ensure_funds(receiver, original.amount)?;
+                    let returned = original.amount.checked_add(original.fee);
+                    wallet.ensure_room(
+                        original.account_number,
+                        returned.ok_or(WalletError::Overflow)?,
+                    )?;
+                }
+                _ => return Err(WalletError::NotReversible(original.id)),
+            }
+            if let (Some(reference), Some(receiver)) = (&original.settlement, original.counterparty)
+            {
+                // Reversing at the switch is safe to repeat, so if the commit
+                // below fails the whole reversal can simply be tried again.
+                let bank = wallet.user(receiver)?.bank;
+                wallet.connector(bank)?.reverse(reference)?;
+            }
+
+            wallet.commit(Record::Reversed {
+                transaction_id: original.id,
+                key,
+                at: wallet.now(),
+            })?;
+            Ok(wallet.receipt(original.account_number))
+        })
+    }
+
+    /// Returns part of a debit to the customer. Refunds on one transaction can
+    /// add up to its amount but no more; the fee is kept. The part of a
+    /// transfer settled with another bank is debited back from that bank.
+    fn refund(
+        &mut self,
+        transaction_id: u64,
+        amount: Money,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        ensure_positive(amount)?;
+        let request = fingerprint(&format!("refund {transaction_id}"), &[], amount);
+        self.idempotent(key, request, |wallet, key| {
+            let original = wallet.adjustable(transaction_id)?;
+            let refundable = original
+                .amount
+                .checked_sub(wallet.adjustments.refunded(original.id))
+                .unwrap_or(Money::ZERO);
+            if amount > refundable {
+                return Err(WalletError::RefundExceedsOriginal {
+                    transaction: original.id,
+                    requested: amount,
+                    refundable,
+                });
+            }
+            match original.kind {
+                TransactionKind::Withdrawal | TransactionKind::Capture => {}
+                TransactionKind::TransferOut => {
+                    let receiver = original
+                        .counterparty
+                        .expect("transfers have a counterparty");
+                    wallet.ensure_funds(receiver, amount)?;
+                }
+                _ => return Err(WalletError::NotRefundable(original.id)),
+            }
+            wallet.ensure_room(original.account_number, amount)?;
+
+            let record = Record::Refunded {
+                transaction_id: original.id,
+                amount,
+                key,
+                at: wallet.now(),
+            };
+            let (Some(reference), Some(receiver)) = (&original.settlement, original.counterparty)
+            else {
+                wallet.commit(record)?;
+                return Ok(wallet.receipt(original.account_number));
+            };
+            let bank = wallet.user(receiver)?.bank;
+            let reference = new_reference(&format!("{reference}-RFD"))
+                .map_err(|err| WalletError::Random(err.to_string()))?;
+            wallet.settle(bank, &reference, |connector| {
+                connector.debit(&reference, receiver, amount)
+            })?;
+            if let Err(err) = wallet.commit(record) {
+                // As in `send`: undo the other bank's side so neither side moves.
+                if let Ok(connector) = wallet.connector(bank) {
+                    let _ = connector.reverse(&reference);
+                }
+                return Err(err);
+            }
+            Ok(wallet.receipt(original.account_number))
+        })
+    }
+
+    /// Opens a chargeback on a withdrawal or capture. The part of it not yet
+    /// refunded is recovered into suspense until the dispute is decided.
+    fn open_dispute(&mut self, transaction_id: u64) -> Result<Dispute, WalletError> {
+        let original = self.adjustable(transaction_id)?;
+        if !matches!(
+            original.kind,
+            TransactionKind::Withdrawal | TransactionKind::Capture
+        ) {
+            return Err(WalletError::NotDisputable(original.id));
+        }
+        let amount = original
+            .amount
+            .checked_sub(self.adjustments.refunded(original.id))
+            .filter(|amount| !amount.is_zero())
+            .ok_or(WalletError::NotDisputable(original.id))?;
+
+        let dispute_id = self.adjustments.next_dispute_id();
+        self.commit(Record::DisputeOpened {
+            dispute_id,
+            transaction_id: original.id,
+            amount,
+            at: self.now(),
+        })?;
+        self.dispute(dispute_id)
+    }
+
+    /// Moves a dispute along `Opened -> UnderReview -> Won | Lost`.
+    fn move_dispute(
+        &mut self,
+        dispute_id: u64,
+        next: DisputeState,
+    ) -> Result<Dispute, WalletError> {
+        let dispute = self.dispute(dispute_id)?;
+        if !dispute.state.can_move_to(next) {
+            return Err(WalletError::InvalidDisputeTransition {
+                dispute: dispute_id,
+                from: dispute.state,
+                to: next,
+            });
+        }
+        if next == DisputeState::Won {
+            self.ensure_room(dispute.account_number, dispute.amount)?;
+        }
+
+        self.commit(Record::DisputeMoved {
+            dispute_id,
+            state: next,
+            at: self.now(),
+        })?;
+        self.dispute(dispute_id)
+    }
+
+    fn dispute(&self, dispute_id: u64) -> Result<Dispute, WalletError> {
+        self.adjustments
+            .dispute(dispute_id)
+            .cloned()
+            .ok_or(WalletError::DisputeNotFound(dispute_id))
+    }
+
+    /// The transaction an adjustment applies to. The credit side of a
+    /// transfer resolves to its debit side so a transfer is only ever
+    /// adjusted once.
+    fn adjustable(&self, transaction_id: u64) -> Result<Transaction, WalletError> {
+        let mut original = self
+            .ledger
+            .get(transaction_id)
+            .ok_or(WalletError::TransactionNotFound(transaction_id))?;
+        if original.kind == TransactionKind::TransferIn
+            && let Some(sent) = original.related.and_then(|id| self.ledger.get(id))
+        {
+            original = sent;
+        }
+
+        if self.adjustments.is_reversed(original.id) {
+            return Err(WalletError::NotReversible(original.id));
+        }
+        // A decided dispute has already settled who keeps the money, so
+        // neither another dispute nor a reversal may reopen it.
+        match self.adjustments.dispute_on(original.id) {
+            Some(dispute) if dispute.state.is_open() => {
+                return Err(WalletError::TransactionDisputed(original.id));
+            }
+            Some(dispute) => {
+                return Err(WalletError::DisputeDecided {
+                    transaction: original.id,
+                    dispute: dispute.id,
+                    state: dispute.state,
+                });
+            }
+            None => {}
+        }
+        Ok(original.clone())
+    }
+
+    fn balance_of(&self, account_number: AccountNumber) -> Option<Balance> {
+        self.ensure_account(account_number).ok()?;
+        Some(Balance {
+            ledger: self.journal.customer_balance(account_number),
+            available: self.available_balance(account_number),
+        })
+    }
+
+    fn available_balance(&self, account_number: AccountNumber) -> Money {
+        let ledger = self.journal.customer_balance(account_number);
+        let held = self.holds.held_for(account_number, self.now());
+        ledger.checked_sub(held).unwrap_or(Money::ZERO)
+    }
+
+    fn history(&self, account_number: AccountNumber, query: &HistoryQuery) -> Option<Page> {
+        if !self.wallet_details.contains_key(&account_number) {
+            return None;
+        }
+        Some(self.ledger.history(account_number, query))
+    }
+
+    /// The end-of-day batch for `date`: accrues a day's interest on every
+    /// savings account, catching up any days a previous run missed, and on
+    /// the last day of a month pays the month's interest. Each date runs
+    /// once, in order, and only once the day is over.
+    fn run_end_of_day(&mut self, date: Date) -> Result<Vec<Receipt>, WalletError> {
+        if let Some(last) = self.last_end_of_day
+            && date <= last
+        {
+            return Err(WalletError::EndOfDayAlreadyRun(date));
+        }
+        let at = self.now();
+        if date >= Date::from_timestamp(at) {
+            return Err(WalletError::EndOfDayInFuture(date));
+        }
+        let recorded = self.ledger.entries().len();
+        self.commit(Record::EndOfDay {
+            day: date.timestamp(),
+            at,
+        })?;
+        Ok(self.ledger.entries()[recorded..]
+            .iter()
+            .map(Receipt::from)
+            .collect())
+    }
+
+    fn savings(&self, account_number: AccountNumber) -> Option<&Savings> {
+        self.wallet_details.get(&account_number)?.savings.as_ref()
+    }
+
+    /// Balance at the end of `day`, from the last transaction before midnight.
+    fn closing_balance(&self, account_number: AccountNumber, day: Date) -> Money {
+        let end = day.next_day().timestamp();
+        self.ledger
+            .entries()
+            .iter()
+            .rev()
+            .find(|t| t.account_number == account_number && t.timestamp < end)
+            .map_or(Money::ZERO, |t| t.balance_after)
+    }
+
+    /// The account's statement for `from` through `to`, both whole days in
+    /// UTC. The opening balance is the balance at midnight starting `from`.
+    fn statement(
+        &self,
+        account_number: AccountNumber,
+        from: Date,
+        to: Date,
+    ) -> Result<Statement, WalletError> {
+        let user = self.user(account_number)?;
+        if from > to {
+            return Err(WalletError::InvalidDateRange { from, to });
+        }
+        let (start, end) = (from.timestamp(), to.next_day().timestamp());
+        let entries = || {
+            self.ledger
+                .entries()
+                .iter()
+                .filter(move |t| t.account_number == account_number)
+        };
+        let opening_balance = entries()
+            .take_while(|t| t.timestamp < start)
+            .last()
+            .map_or(Money::ZERO, |t| t.balance_after);
+        let lines: Vec<&Transaction> = entries()
+            .filter(|t| (start..end).contains(&t.timestamp))
+            .collect();
+        Statement::new(
+            account_number,
+            &user.name,
+            user.bank,
+            (from, to),
+            opening_balance,
+            &lines,
+        )
+        .ok_or(WalletError::Overflow)
+    }
+
+    fn transaction(&self, id: u64) -> Option<&Transaction> {
+        self.ledger.get(id)
+    }
+
+    fn trial_balance(&self) -> TrialBalance {
+        self.journal.trial_balance()
+    }
+
+    /// Every double-entry journal entry, oldest first.
+    fn journal_entries(&self) -> &[JournalEntry] {
+        self.journal.entries()
+    }
+
+    fn ensure_account(&self, account_number: AccountNumber) -> Result<(), WalletError> {
+        self.user(account_number).map(|_| ())
+    }
+
+    fn user(&self, account_number: AccountNumber) -> Result<&User, WalletError> {
+        self.wallet_details
+            .get(&account_number)
+            .ok_or(WalletError::AccountNotFound(account_number))
+    }
+
+    /// Inter-bank transfers the account has sent this month that still
+    /// stand; a reversed transfer gives its free slot back.
+    fn inter_bank_transfers_this_month(&self, account_number: AccountNumber, at: u64) -> u32 {
+        let Some(sender) = self.wallet_details.get(&account_number) else {
+            return 0;
+        };
+        let month_start = Date::from_timestamp(at).month_start().timestamp();
+        let receiver_bank = |counterparty| self.wallet_details.get(&counterparty).map(|u| u.bank);
+
+        self.ledger
+            .entries()
+            .iter()
+            .filter(|t| t.account_number == account_number && t.timestamp >= month_start)
+            .filter(|t| t.kind == TransactionKind::TransferOut)
+            .filter(|t| !self.adjustments.is_reversed(t.id))
+            .filter(|t| t.counterparty.and_then(receiver_bank) != Some(sender.bank))
+            .count() as u32
+    }
+
+    fn ensure_funds(
+        &self,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), WalletError> {
+        self.ensure_can_debit(account_number)?;
+        let penalty = self.savings(account_number).map_or(Money::ZERO, |savings| {
+            savings.early_withdrawal_penalty(self.now())
+        });
+        let needed = amount.checked_add(penalty).ok_or(WalletError::Overflow)?;
+        let available = self.available_balance(account_number);
+        if available < needed {
+            return Err(WalletError::InsufficientFunds {
+                account: account_number,
+                needed,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Tier limits on money coming in: the single-transaction limit and the
+    /// maximum balance the account may hold afterwards.
+    fn ensure_can_receive(
+        &self,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), WalletError> {
+        self.ensure_can_credit(account_number)?;
+        let limits = self.user(account_number)?.tier.limits();
+        if amount > limits.single_transaction {
+            return Err(self.limit_exceeded(account_number, LimitKind::SingleTransaction, amount));
+        }
+        self.ensure_room(account_number, amount)
+    }
+
+    /// Tier limits on money going out: the single-transaction limit and the
+    /// running total of today's debits (UTC day).
+    fn ensure_can_send(
+        &self,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), WalletError> {
+        let limits = self.user(account_number)?.tier.limits();
+        if amount > limits.single_transaction {
+            return Err(self.limit_exceeded(account_number, LimitKind::SingleTransaction, amount));
+        }
+        let sent_today = self
+            .sent_today(account_number)?
+            .checked_add(amount)
+            .ok_or(WalletError::Overflow)?;
+        if sent_today > limits.daily {
+            return Err(self.limit_exceeded(account_number, LimitKind::Daily, sent_today));
+        }
+        Ok(())
+    }
+
+    /// What the account has sent so far today (UTC day), naira sold for
+    /// other currencies included. Live holds count as sent from the moment
+    /// they are placed, so capturing one never has to be checked again.
+    fn sent_today(&self, account_number: AccountNumber) -> Result<Money, WalletError> {
+        let now = self.now();
+        let today = Date::from_timestamp(now).timestamp();
+        let mut balance = Money::ZERO;
+        let mut sent = Money::ZERO;
+        for t in self
+            .ledger
+            .entries()
+            .iter()
+            .filter(|t| t.account_number == account_number)
+        {
+            // A conversion line does not say which way it went; selling
+            // naira is the one that lowered the balance.
+            let debit = match t.kind {
+                TransactionKind::Withdrawal
+                | TransactionKind::TransferOut
+                | TransactionKind::Capture => true,
+                TransactionKind::Conversion => t.balance_after < balance,
+                _ => false,
+            };
+            if debit && t.timestamp >= today {
+                sent = sent.checked_add(t.amount).ok_or(WalletError::Overflow)?;
+            }
+            balance = t.balance_after;
+        }
+        sent.checked_add(self.holds.held_for(account_number, now))
+            .ok_or(WalletError::Overflow)
+    }
+
+    fn limit_exceeded(
+        &self,
+        account_number: AccountNumber,
+        kind: LimitKind,
+        attempted: Money,
+    ) -> WalletError {
+        let limits = self
+            .wallet_details
+            .get(&account_number)
+            .map(|user| user.tier.limits())
+            .unwrap_or(KycTier::default().limits());
+        let limit = match kind {
+            LimitKind::MaxBalance => limits.max_balance,
+            LimitKind::SingleTransaction => limits.single_transaction,
+            LimitKind::Daily => limits.daily,
+        };
+        WalletError::LimitExceeded {
+            account: account_number,
+            kind,
+            limit,
+            attempted,
+        }
+    }
+
+    /// Money coming back to an account, such as a refund or a reversal, is
+    /// not a new transaction, so only the maximum balance applies to it.
+    fn ensure_room(&self, account_number: AccountNumber, amount: Money) -> Result<(), WalletError> {
+        self.ensure_can_credit(account_number)?;
+        let balance_after = self
+            .journal
+            .customer_balance(account_number)
+            .checked_add(amount)
+            .ok_or(WalletError::Overflow)?;
+        if balance_after > self.user(account_number)?.tier.limits().max_balance {
+            return Err(self.limit_exceeded(account_number, LimitKind::MaxBalance, balance_after));
+        }
+        Ok(())
+    }
+
+    /// Receipt for the operation that was just committed against `account_number`.
+    fn receipt(&self, account_number: AccountNumber) -> Receipt {
+        let transaction = self
+            .ledger
+            .latest_for(account_number)
+            .expect("a committed operation always records a transaction");
+        Receipt::from(transaction)
+    }
+
+    /// Makes `record` durable, then applies it. Nothing changes in memory if
+    /// the write fails, so memory never runs ahead of the file. A record
+    /// dated before the last one is refused, which keeps history in time
+    /// order for `as_of`.
+    fn commit(&mut self, record: Record) -> Result<(), WalletError> {
+        if let Some(last) = self.history.last_at()
+            && record.at() < last
+        {
+            return Err(WalletError::OutOfOrder {
+                at: record.at(),
+                last,
+            });
+        }
+        if let Some(storage) = self.storage.as_mut() {
+            storage.append(&record)?;
+        }
+        self.audit.append(&record)?;
+        self.apply(record.clone());
+        self.history.push(record);
+        Ok(())
+    }
+
+    /// Applies an already validated record. This is shared by live operations
+    /// and by `open` replaying the log, so both build exactly the same state.
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::AccountAdded {
+                name,
+                bank,
+                account_number,
+                savings,
+                at,
+            } => {
+                let mut user = User::new(name, bank, account_number);
+                user.opened_at = at;
+                user.savings = savings.map(|product| Savings::new(product, at));
+                self.wallet_details.insert(account_number, user);
+            }
+            Record::AccountStateChanged {
+                account_number,
+                state,
+                reason,
+                at,
+            } => {
+                let Some(user) = self.wallet_details.get_mut(&account_number) else {
+                    return;
+                };
+                self.state_changes.push(StateChange {
+                    account_number,
+                    from: user.state,
+                    to: state,
+                    reason,
+                    at,
+                });
+                user.state = state;
+            }
+            Record::TierUpgraded {
+                account_number,
+                tier,
+                ..
+            } => {
+                if let Some(user) = self.wallet_details.get_mut(&account_number) {
+                    user.tier = tier;
+                }
+            }
+            Record::PinSet {
+                account_number,
+                credential,
+                ..
+            } => self.pins.set(account_number, credential),
+            Record::PinFailed {
+                account_number,
+                locked,
+                ..
+            } => self.pins.record_failure(account_number, locked),
+            Record::PinVerified { account_number, .. } => self.pins.record_success(account_number),
+            Record::PinResetRequested {
+                account_number,
+                token_hash,
+                expires_at,
+                ..
+            } => self
+                .pins
+                .start_reset(account_number, token_hash, expires_at),
+            Record::Deposited {
+                account_number,
+                amount,
+                key,
+                at,
+            } => {
+                let customer = LedgerAccount::Customer(account_number);
+                self.journal.transfer(
+                    "deposit",
+                    LedgerAccount::CashInTransit,
+                    customer,
+                    amount,
+                    at,
+                );
+                let line = self.line(account_number, TransactionKind::Deposit, amount, at);
+                let id = self.ledger.record(line);
+                self.remember_key(key, Outcome::Transaction(id), at);
+            }
+            Record::Withdrawn {
+                account_number,
+                amount,
+                key,
+                at,
+            } => {
+                self.forfeit_interest(account_number, amount, at);
+                let customer = LedgerAccount::Customer(account_number);
+                self.journal.transfer(
+                    "withdrawal",
+                    customer,
+                    LedgerAccount::CashInTransit,
+                    amount,
+                    at,
+                );
+                let line = self.line(account_number, TransactionKind::Withdrawal, amount, at);
+                let id = self.ledger.record(line);
+                self.remember_key(key, Outcome::Transaction(id), at);
+            }
+            Record::Transferred {
+                from,
+                to,
+                amount,
+                fee,
+                standing_order,
+                narration,
+                settlement,
+                payout,
+                key,
+                at,
+            } => {
+                if let Some(order_id) = standing_order {
+                    self.standing_orders.advance(order_id);
+                }
+                self.forfeit_interest(from, amount.checked_add(fee).unwrap_or(Money::MAX), at);
+                // A single journal entry carries every leg, so the debit, the
+                // credit and the fee cannot be separated.
+                let mut postings = vec![
+                    Posting::debit(LedgerAccount::Customer(from), amount),
+                    Posting::credit(LedgerAccount::Customer(to), amount),
+                ];
+                if !fee.is_zero() {
+                    postings.push(Posting::debit(LedgerAccount::Customer(from), fee));
+                    postings.push(Posting::credit(LedgerAccount::Fees, fee));
+                }
+                self.journal.post("transfer", postings, at);
+
+                let sent = self
+                    .line(from, TransactionKind::TransferOut, amount, at)
+                    .counterparty(to)
+                    .fee(fee)
+                    .narration(narration.clone())
+                    .settlement(settlement);
+                let sent_id = self.ledger.record(sent);
+                if let Some(payout) = payout {
+                    self.paid_rows.record(from, payout, sent_id);
+                }
+                let received = self
+                    .line(to, TransactionKind::TransferIn, amount, at)
+                    .counterparty(from)
+                    .related(sent_id)
+                    .narration(narration);
+                self.ledger.record(received);
+                self.remember_key(key, Outcome::Transaction(sent_id), at);
+            }
+            Record::HoldPlaced {
+                hold_id,
+                account_number,
+                amount,
+                expires_at,
+                key,
+                at,
+            } => {
+                self.holds.place(Hold {
+                    id: hold_id,
+                    account_number,
+                    amount,
+                    expires_at,
+                    status: HoldStatus::Active,
+                });
+                self.remember_key(key, Outcome::Hold(hold_id), at);
+            }
+            Record::HoldCaptured {
+                hold_id,
+                amount,
+                key,
+                at,
+            } => {
+                let Some(account_number) = self.holds.get(hold_id).map(|h| h.account_number) else {
+                    return;
+                };
+                self.holds.set_status(hold_id, HoldStatus::Captured);
+                self.forfeit_interest(account_number, amount, at);
+                self.journal.transfer(
+                    "hold capture",
+                    LedgerAccount::Customer(account_number),
+                    LedgerAccount::CashInTransit,
+                    amount,
+                    at,
+                );
+                let line = self.line(account_number, TransactionKind::Capture, amount, at);
+                let id = self.ledger.record(line);
+                self.remember_key(key, Outcome::Transaction(id), at);
+            }
+            Record::HoldVoided { hold_id, .. } => {
+                self.holds.set_status(hold_id, HoldStatus::Voided);
+            }
+            Record::HoldExpired { hold_id, .. } => {
+                self.holds.set_status(hold_id, HoldStatus::Expired);
+            }
+            Record::Reversed {
+                transaction_id,
+                key,
+                at,
+            } => {
+                self.apply_reversal(transaction_id, at);
+                self.remember_adjustment(key, transaction_id, at);
+            }
+            Record::StandingOrderCreated {
+                order_id,
+                from,
+                to,
+                amount,
+                frequency,
+                first_due,
+                key,
+                at,
+            } => {
+                self.standing_orders.add(StandingOrder {
+                    id: order_id,
+                    from,
+                    to,
+                    amount,
+                    frequency,
+                    next_due: Date::from_timestamp(first_due),
+                    status: OrderStatus::Active,
+                });
+                self.remember_key(key, Outcome::StandingOrder(order_id), at);
+            }
+            Record::StandingOrderCancelled { order_id, .. } => {
+                self.standing_orders.cancel(order_id)
+            }
+            Record::StandingOrderMissed { order_id, .. } => self.standing_orders.advance(order_id),
+            Record::CurrencyDeposited {
+                account_number,
+                currency,
+                amount,
+                key,
+                at,
+            } => {
+                self.journal.transfer(
+                    "currency deposit",
+                    LedgerAccount::ForeignCash(currency),
+                    holding(account_number, currency),
+                    amount,
+                    at,
+                );
+                if let Ok(balance) = self.balance_in(account_number, currency) {
+                    self.remember_key(key, Outcome::Balance(balance), at);
+                }
+            }
+            Record::Converted {
+                account_number,
+                from,
+                to,
+                sold,
+                bought,
+                mid,
+                key,
+                at,
+            } => {
+                self.apply_conversion(account_number, (from, sold), (to, bought, mid), at);
+                let quote = FxQuote {
+                    sold: Amount::new(from, sold),
+                    bought: Amount::new(to, bought),
+                    mid: Amount::new(to, mid),
+                    spread: Amount::new(to, mid.checked_sub(bought).unwrap_or(Money::ZERO)),
+                };
+                self.remember_key(key, Outcome::Conversion(quote), at);
+            }
+            Record::EndOfDay { day, at } => self.apply_end_of_day(Date::from_timestamp(day), at),
+            Record::Refunded {
+                transaction_id,
+                amount,
+                key,
+                at,
+            } => {
+                self.apply_refund(transaction_id, amount, at);
+                self.remember_adjustment(key, transaction_id, at);
+            }
+            Record::DisputeOpened {
+                dispute_id,
+                transaction_id,
+                amount,
+                at,
+            } => {
+                let Some(account_number) =
+                    self.ledger.get(transaction_id).map(|t| t.account_number)
+                else {
+                    return;
+                };
+                self.journal.transfer(
+                    "dispute opened",
+                    LedgerAccount::CashInTransit,
+                    LedgerAccount::Suspense,
+                    amount,
+                    at,
+                );
+                self.adjustments.open_dispute(Dispute {
+                    id: dispute_id,
+                    transaction_id,
+                    account_number,
+                    amount,
+                    state: DisputeState::Opened,
+                });
+            }
+            Record::DisputeMoved {
+                dispute_id,
+                state,
+                at,
+            } => self.apply_dispute_move(dispute_id, state, at),
+        }
+    }
+
+    fn remember_key(&mut self, key: Option<IdempotencyKey>, outcome: Outcome, at: u64) {
+        if let Some(key) = key {
+            self.idempotency_keys.remember(key, outcome, at);
+        }
+    }
+
+    // A reversal or refund answers with the customer's newest line on the
+    // adjusted transaction's account, as `reverse` and `refund` do.
+    fn remember_adjustment(&mut self, key: Option<IdempotencyKey>, transaction_id: u64, at: u64) {
+        let latest = self
+            .ledger
+            .get(transaction_id)
+            .and_then(|original| self.ledger.latest_for(original.account_number));
+        if let Some(latest) = latest {
+            self.remember_key(key, Outcome::Transaction(latest.id), at);
+        }
+    }
+
+    /// Each currency gets its own balanced entry: the customer sells into the
+    /// FX position in one currency and buys out of it in the other, and the
+    /// spread between the mid value and what they get is income.
+    fn apply_conversion(
+        &mut self,
+        account_number: AccountNumber,
+        (from, sold): (Currency, Money),
+        (to, bought, mid): (Currency, Money, Money),
+        at: u64,
+    ) {
+        self.journal.transfer(
+            "fx sale",
+            holding(account_number, from),
+            LedgerAccount::FxPosition(from),
+            sold,
+            at,
+        );
+        let spread = mid.checked_sub(bought).unwrap_or(Money::ZERO);
+        let mut postings = vec![
+            Posting::debit(LedgerAccount::FxPosition(to), bought),
+            Posting::credit(holding(account_number, to), bought),
+        ];
+        if !spread.is_zero() {
+            postings.push(Posting::debit(LedgerAccount::FxPosition(to), spread));
+            postings.push(Posting::credit(LedgerAccount::FxIncome(to), spread));
+        }
+        self.journal.post("fx purchase", postings, at);
+
+        // Only naira movements appear in the customer's transaction history.
+        if from == Currency::Ngn {
+            let line = self.line(account_number, TransactionKind::Conversion, sold, at);
+            self.ledger.record(line);
+        } else if to == Currency::Ngn {
+            let line = self.line(account_number, TransactionKind::Conversion, bought, at);
+            self.ledger.record(line);
+        }
+    }
+
+    fn apply_end_of_day(&mut self, date: Date, at: u64) {
+        self.last_end_of_day = Some(date);
+        // Sorted so that replay records interest in the same order.
+        let mut savers: Vec<AccountNumber> = self
+            .wallet_details
+            .values()
+            .filter(|user| user.savings.is_some() && user.state != AccountState::Closed)
+            .map(|user| user.account_number)
+            .collect();
+        savers.sort();
+
+        for account_number in savers {
+            let Some(savings) = self.savings(account_number) else {
+                continue;
+            };
+            let mut day = match savings.accrued_through {
+                Some(through) => through.next_day(),
+                None => Date::from_timestamp(self.wallet_details[&account_number].opened_at),
+            };
+            // Interest paid earlier in this run is not yet in any closing
+            // balance, since it is stamped with the run's time.
+            let mut paid_this_run = Money::ZERO;
+            while day <= date {
+                let closing = self
+                    .closing_balance(account_number, day)
+                    .checked_add(paid_this_run)
+                    .unwrap_or(Money::MAX);
+                let Some(savings) = self.savings_mut(account_number) else {
+                    break;
+                };
+                savings.accrue(day, closing);
+                let month_end = day.next_day().day == 1;
+                let interest = if month_end {
+                    savings.take_interest(day.next_day().timestamp())
+                } else {
+                    Money::ZERO
+                };
+                if !interest.is_zero() {
+                    self.journal.transfer(
+                        "interest",
+                        LedgerAccount::InterestExpense,
+                        LedgerAccount::Customer(account_number),
+                        interest,
+                        at,
+                    );
+                    let line = self.line(account_number, TransactionKind::Interest, interest, at);
+                    self.ledger.record(line);
+                    paid_this_run = paid_this_run.checked_add(interest).unwrap_or(Money::MAX);
+                }
+                day = day.next_day();
+            }
+        }
+    }
+
+    /// Claws back a fixed-term account's interest when `debit` is taken out
+    /// before the term ends. Never takes more than the debit leaves behind.
+    fn forfeit_interest(&mut self, account_number: AccountNumber, debit: Money, at: u64) {
+        let balance = self.journal.customer_balance(account_number);
+        let Some(savings) = self.savings_mut(account_number) else {
+            return;
+        };
+        let left = balance.checked_sub(debit).unwrap_or(Money::ZERO);
+        savings.debited(left);
+        if !savings.is_locked(at) {
+            return;
+        }
+        let forfeited = savings.break_lock(left);
+        if forfeited.is_zero() {
+            return;
+        }
+        self.journal.transfer(
+            "interest forfeited",
+            LedgerAccount::Customer(account_number),
+            LedgerAccount::InterestExpense,
+            forfeited,
+            at,
+        );
+        let line = self.line(
+            account_number,
+            TransactionKind::InterestForfeited,
+            forfeited,
+            at,
+        );
+        self.ledger.record(line);
+    }
+
+    fn savings_mut(&mut self, account_number: AccountNumber) -> Option<&mut Savings> {
+        self.wallet_details
+            .get_mut(&account_number)?
+            .savings
+            .as_mut()
+    }
+
+    fn apply_reversal(&mut self, transaction_id: u64, at: u64) {
+        let Some(original) = self.ledger.get(transaction_id).cloned() else {
+            return;
+        };
+        let customer = LedgerAccount::Customer(original.account_number);
+        let mut credited = original.amount;
+        match original.kind {
+            TransactionKind::Deposit => {
+                self.journal.transfer(
+                    "reversal",
+                    customer,
+                    LedgerAccount::CashInTransit,
+                    original.amount,
+                    at,
+                );
+            }
+            TransactionKind::Withdrawal | TransactionKind::Capture => {
+                self.journal.transfer(
+                    "reversal",
+                    LedgerAccount::CashInTransit,
+                    customer,
+                    original.amount,
+                    at,
+                );
+            }
+            TransactionKind::TransferOut => {
+                let Some(receiver) = original.counterparty else {
+                    return;
+                };
+                let mut postings = vec![
+                    Posting::debit(LedgerAccount::Customer(receiver), original.amount),
+                    Posting::credit(customer, original.amount),
+                ];
+                if !original.fee.is_zero() {
+                    postings.push(Posting::debit(LedgerAccount::Fees, original.fee));
+                    postings.push(Posting::credit(customer, original.fee));
+                    credited = credited.checked_add(original.fee).unwrap_or(Money::MAX);
+                }
+                self.journal.post("reversal", postings, at);
+                let line = self
+                    .line(receiver, TransactionKind::Reversal, original.amount, at)
+                    .counterparty(original.account_number)
+                    .related(original.id);
+                self.ledger.record(line);
+            }
+            _ => return,
+        }
+
+        self.adjustments.mark_reversed(original.id);
+        let line = self
+            .line(
+                original.account_number,
+                TransactionKind::Reversal,
+                credited,
+                at,
+            )
+            .related(original.id);
+        self.ledger.record(line);
+    }
+
+    fn apply_refund(&mut self, transaction_id: u64, amount: Money, at: u64) {
+        let Some(original) = self.ledger.get(transaction_id).cloned() else {
+            return;
+        };
+        let customer = LedgerAccount::Customer(original.account_number);
+        match (original.kind, original.counterparty) {
+            (TransactionKind::TransferOut, Some(receiver)) => {
+                self.journal.transfer(
+                    "refund",
+                    LedgerAccount::Customer(receiver),
+                    customer,
+                    amount,
+                    at,
+                );
+                let line = self
+                    .line(receiver, TransactionKind::Refund, amount, at)
+                    .counterparty(original.account_number)
+                    .related(original.id);
+                self.ledger.record(line);
+            }
+            _ => {
+                self.journal
+                    .transfer("refund", LedgerAccount::CashInTransit, customer, amount, at);
+            }
+        }
+
+        self.adjustments.add_refund(original.id, amount);
+        let line = self
+            .line(original.account_number, TransactionKind::Refund, amount, at)
+            .related(original.id);
+        self.ledger.record(line);
+    }
+
+    fn apply_dispute_move(&mut self, dispute_id: u64, state: DisputeState, at: u64) {
+        let Some(dispute) = self.adjustments.dispute(dispute_id).cloned() else {
+            return;
+        };
+        self.adjustments.set_dispute_state(dispute_id, state);
+        match state {
+            DisputeState::Won => {
+                self.journal.transfer(
+                    "dispute won",
+                    LedgerAccount::Suspense,
+                    LedgerAccount::Customer(dispute.account_number),
+                    dispute.amount,
+                    at,
+                );
+                self.adjustments
+                    .add_refund(dispute.transaction_id, dispute.amount);
+                let line = self
+                    .line(
+                        dispute.account_number,
+                        TransactionKind::Chargeback,
+                        dispute.amount,
+                        at,
+                    )
+                    .related(dispute.transaction_id);
+                self.ledger.record(line);
+            }
+            DisputeState::Lost => {
+                self.journal.transfer(
+                    "dispute lost",
+                    LedgerAccount::Suspense,
+                    LedgerAccount::CashInTransit,
+                    dispute.amount,
+                    at,
+                );
+            }
+            DisputeState::Opened | DisputeState::UnderReview => {}
+        }
+    }
+
+    /// Credits `account_number` at `bank` once the bank confirms the
+    /// account holder's name, and returns once the money has definitely
+    /// arrived.
+    fn settle_with(
+        &mut self,
+        bank: Bank,
+        reference: &str,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), WalletError> {
+        let expected = self.user(account_number)?.name.clone();
+        let found = self.connector(bank)?.name_enquiry(account_number)?;
+        if !found.eq_ignore_ascii_case(&expected) {
+            return Err(WalletError::NameMismatch {
+                account: account_number,
+                expected,
+                found,
+            });
+        }
+        self.settle(bank, reference, |connector| {
+            connector.credit(reference, account_number, amount)
+        })
+    }
+
+    /// Sends `request` to `bank` and returns once it has definitely been
+    /// applied. A timeout is settled by requerying; if the outcome is still
+    /// unknown the request is reversed so it fails cleanly.
+    fn settle<F>(&mut self, bank: Bank, reference: &str, request: F) -> Result<(), WalletError>
+    where
+        F: FnOnce(&mut dyn BankConnector) -> Result<(), ConnectorError>,
+    {
+        let connector = self.connector(bank)?;
+        match request(&mut *connector) {
+            Ok(()) => Ok(()),
+            Err(ConnectorError::Timeout(_)) => match connector.requery(reference) {
+                Ok(SettlementStatus::Successful) => Ok(()),
+                Ok(
+                    SettlementStatus::Failed
+                    | SettlementStatus::Reversed
+                    | SettlementStatus::NotFound,
+                ) => Err(ConnectorError::Rejected(bank, "request was not applied".into()).into()),
+                Err(err) => {
+                    connector
+                        .reverse(reference)
+                        .map_err(|_| WalletError::SettlementUnknown(reference.to_string()))?;
+                    Err(err.into())
+                }
+            },
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn connector(&mut self, bank: Bank) -> Result<&mut dyn BankConnector, WalletError> {
+        match self.connectors.get_mut(&bank) {
+            Some(connector) => Ok(connector.as_mut()),
+            None => Err(WalletError::NoConnector(bank)),
+        }
+    }
+
+    /// A ledger line for `account_number` carrying its current balance.
+    fn line(
+        &self,
+        account_number: AccountNumber,
+        kind: TransactionKind,
+        amount: Money,
+        at: u64,
+    ) -> Transaction {
+        let balance = self.journal.customer_balance(account_number);
+        Transaction::new(account_number, kind, amount, balance, at)
+    }
+}
+
+/// The ledger account that holds a customer's money in `currency`.
+fn holding(account_number: AccountNumber, currency: Currency) -> LedgerAccount {
+    match currency {
+        Currency::Ngn => LedgerAccount::Customer(account_number),
+        _ => LedgerAccount::CustomerForeign(account_number, currency),
+    }
+}
+
+/// A movement of nothing is always a mistake by the caller, so every
+/// operation that moves money refuses it before doing anything else.
+fn ensure_positive(amount: Money) -> Result<(), WalletError> {
+    if amount.is_zero() {
+        return Err(WalletError::ZeroAmount);
+    }
+    Ok(())
+}
 
-    println!("Deposit status: {:?}", deposit_status);
-    println!("Withdraw status: {:?}", withdraw_status);
-    println!("1001 balance: {:?}", wallet.balance_of(1001));
-    println!("1002 balance: {:?}", wallet.balance_of(1002));
+/// The wallet's command-line tool; `args` leaves out the program name. Run
+/// with `help` for the commands.
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(cli::run(args)?)
 }
@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+/// Where a dispute is in its lifecycle. `Opened` moves the disputed amount
+/// into suspense; `Won` releases it to the customer and `Lost` sends it back
+/// to where it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisputeState {
+    Opened,
+    UnderReview,
+    Won,
+    Lost,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dispute {
+    pub id: u64,
+    pub transaction_id: u64,
+    pub account_number: AccountNumber,
+    pub amount: Money,
+    pub state: DisputeState,
+}
+
+/// Everything done after the fact to a transaction: full reversals, partial
+/// refunds and disputes, keyed by the original transaction id.
+#[derive(Debug, Clone, Default)]
+pub struct Adjustments {
+    reversed: HashSet<u64>,
+    refunded: HashMap<u64, Money>,
+    disputes: BTreeMap<u64, Dispute>,
+}
+
+impl DisputeState {
+    pub fn can_move_to(self, next: DisputeState) -> bool {
+        matches!(
+            (self, next),
+            (DisputeState::Opened, DisputeState::UnderReview)
+                | (DisputeState::UnderReview, DisputeState::Won)
+                | (DisputeState::UnderReview, DisputeState::Lost)
+        )
+    }
+
+    pub fn is_open(self) -> bool {
+        matches!(self, DisputeState::Opened | DisputeState::UnderReview)
+    }
+}
+
+impl Adjustments {
+    pub fn new() -> Self {
+        Self {
+            reversed: HashSet::new(),
+            refunded: HashMap::new(),
+            disputes: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_reversed(&self, transaction_id: u64) -> bool {
+        self.reversed.contains(&transaction_id)
+    }
+
+    pub fn refunded(&self, transaction_id: u64) -> Money {
+        self.refunded
+            .get(&transaction_id)
+            .copied()
+            .unwrap_or(Money::ZERO)
+    }
+
+    pub fn mark_reversed(&mut self, transaction_id: u64) {
+        self.reversed.insert(transaction_id);
+    }
+
+    pub fn add_refund(&mut self, transaction_id: u64, amount: Money) {
+        let total = self.refunded(transaction_id).checked_add(amount);
+        self.refunded
+            .insert(transaction_id, total.unwrap_or(Money::MAX));
+    }
+
+    pub fn next_dispute_id(&self) -> u64 {
+        self.disputes.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    pub fn open_dispute(&mut self, dispute: Dispute) {
+        self.disputes.insert(dispute.id, dispute);
+    }
+
+    pub fn dispute(&self, id: u64) -> Option<&Dispute> {
+        self.disputes.get(&id)
+    }
+
+    pub fn set_dispute_state(&mut self, id: u64, state: DisputeState) {
+        if let Some(dispute) = self.disputes.get_mut(&id) {
+            dispute.state = state;
+        }
+    }
+
+    /// The dispute on `transaction_id`, open or decided. A transaction is
+    /// disputed at most once.
+    pub fn dispute_on(&self, transaction_id: u64) -> Option<&Dispute> {
+        self.disputes
+            .values()
+            .find(|d| d.transaction_id == transaction_id)
+    }
+}
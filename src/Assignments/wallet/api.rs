@@ -0,0 +1,599 @@
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::thread;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::error::WalletError;
+use super::ledger::{HistoryQuery, Transaction, TransactionKind};
+use super::money::Money;
+use super::nuban::AccountNumber;
+use super::service::WalletHandle;
+use super::{Balance, Bank, Receipt, User, Wallet};
+
+/// The OpenAPI 3 description of every route below, served at `/openapi.json`.
+const OPENAPI: &str = include_str!("openapi.json");
+
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+const MAX_PAGE_SIZE: usize = 100;
+const WORKERS: usize = 4;
+
+/// Why a request was turned away. Wallet errors keep their own message; the
+/// status code and the machine-readable `code` come from `status` and `code`.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("no route for {method} {path}")]
+    NotFound { method: String, path: String },
+
+    #[error("{method} is not allowed on {path}")]
+    MethodNotAllowed { method: String, path: String },
+
+    #[error("request body must be application/json")]
+    UnsupportedMediaType,
+
+    #[error("request body is larger than {MAX_BODY_BYTES} bytes")]
+    PayloadTooLarge,
+
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpenAccount {
+    name: String,
+    bank: Bank,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetPin {
+    pin: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Deposit {
+    amount: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Withdrawal {
+    amount: u64,
+    pin: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Transfer {
+    from: String,
+    to: String,
+    amount: u64,
+    pin: String,
+}
+
+/// Serves the JSON API on `address` until the process stops. Requests are
+/// handled on a few threads, and every wallet operation goes through
+/// `handle`, so they are applied one at a time like any other caller's.
+pub fn serve(handle: WalletHandle, address: &str) -> io::Result<()> {
+    serve_on(Server::http(address).map_err(io::Error::other)?, handle)
+}
+
+/// Like `serve`, on a server that is already bound, such as one on an
+/// ephemeral port.
+pub fn serve_on(server: Server, handle: WalletHandle) -> io::Result<()> {
+    let server = Arc::new(server);
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let handle = handle.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&handle, request);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| io::Error::other("API worker panicked"))?;
+    }
+    Ok(())
+}
+
+fn respond(handle: &WalletHandle, mut request: Request) {
+    let (status, body) = match route(handle, &mut request) {
+        Ok(ok) => ok,
+        Err(err) => (
+            err.status(),
+            json!({ "error": { "code": err.code(), "message": err.to_string() } }),
+        ),
+    };
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+    // 204 must not carry a body.
+    let body = if status == 204 {
+        String::new()
+    } else {
+        body.to_string()
+    };
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    // The client may have hung up; there is nobody left to tell.
+    let _ = request.respond(response);
+}
+
+fn route(handle: &WalletHandle, request: &mut Request) -> Result<(u16, Value), ApiError> {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let not_allowed = || ApiError::MethodNotAllowed {
+        method: method.to_string(),
+        path: path.to_string(),
+    };
+
+    match segments.as_slice() {
+        ["openapi.json"] => match method {
+            Method::Get => Ok((
+                200,
+                serde_json::from_str(OPENAPI).map_err(WalletError::from)?,
+            )),
+            _ => Err(not_allowed()),
+        },
+        ["accounts"] => match method {
+            Method::Get => {
+                let accounts = handle.call(|wallet| {
+                    wallet
+                        .accounts()
+                        .into_iter()
+                        .map(|user| account_json(user, wallet.balance_of(user.account_number)))
+                        .collect::<Vec<_>>()
+                })?;
+                Ok((200, json!({ "accounts": accounts })))
+            }
+            Method::Post => {
+                let body: OpenAccount = read_json(request)?;
+                let name = body.name.trim().to_string();
+                if name.is_empty() {
+                    return Err(ApiError::BadRequest("name must not be empty".to_string()));
+                }
+                let account = handle.call(move |wallet| {
+                    let account_number = wallet.open_account(&name, body.bank)?;
+                    account(wallet, account_number)
+                })??;
+                Ok((201, account))
+            }
+            _ => Err(not_allowed()),
+        },
+        ["accounts", account_number] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Get => Ok((
+                    200,
+                    handle.call(move |wallet| account(wallet, account_number))??,
+                )),
+                _ => Err(not_allowed()),
+            }
+        }
+        ["accounts", account_number, "balance"] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Get => {
+                    let balance = handle
+                        .balance_of(account_number)?
+                        .ok_or(WalletError::AccountNotFound(account_number))?;
+                    Ok((200, balance_json(balance)))
+                }
+                _ => Err(not_allowed()),
+            }
+        }
+        ["accounts", account_number, "pin"] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Put => {
+                    let body: SetPin = read_json(request)?;
+                    handle.call(move |wallet| wallet.set_pin(account_number, &body.pin))??;
+                    Ok((204, Value::Null))
+                }
+                _ => Err(not_allowed()),
+            }
+        }
+        ["accounts", account_number, "transactions"] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Get => {
+                    let query = history_query(query)?;
+                    let page = handle
+                        .call(move |wallet| wallet.history(account_number, &query))?
+                        .ok_or(WalletError::AccountNotFound(account_number))?;
+                    Ok((
+                        200,
+                        json!({
+                            "items": page.items.iter().map(transaction_json).collect::<Vec<_>>(),
+                            "total": page.total,
+                            "offset": page.offset,
+                            "limit": page.limit,
+                        }),
+                    ))
+                }
+                _ => Err(not_allowed()),
+            }
+        }
+        ["accounts", account_number, "deposits"] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Post => {
+                    let key = idempotency_key(request)?;
+                    let body: Deposit = read_json(request)?;
+                    let amount = positive(body.amount)?;
+                    let receipt = handle.deposit_to(account_number, amount, key.as_deref())?;
+                    Ok((201, receipt_json(&receipt)))
+                }
+                _ => Err(not_allowed()),
+            }
+        }
+        ["accounts", account_number, "withdrawals"] => {
+            let account_number = parse_account(account_number)?;
+            match method {
+                Method::Post => {
+                    let key = idempotency_key(request)?;
+                    let body: Withdrawal = read_json(request)?;
+                    let amount = positive(body.amount)?;
+                    let receipt =
+                        handle.withdraw_from(account_number, amount, &body.pin, key.as_deref())?;
+                    Ok((201, receipt_json(&receipt)))
+                }
+                _ => Err(not_allowed()),
+            }
+        }
+        ["transfers"] => match method {
+            Method::Post => {
+                let key = idempotency_key(request)?;
+                let body: Transfer = read_json(request)?;
+                let from = parse_account(&body.from)?;
+                let to = parse_account(&body.to)?;
+                let amount = positive(body.amount)?;
+                let receipt = handle.transfer(from, to, amount, &body.pin, key.as_deref())?;
+                Ok((201, receipt_json(&receipt)))
+            }
+            _ => Err(not_allowed()),
+        },
+        _ => Err(ApiError::NotFound {
+            method: method.to_string(),
+            path: path.to_string(),
+        }),
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound { .. } => 404,
+            ApiError::MethodNotAllowed { .. } => 405,
+            ApiError::UnsupportedMediaType => 415,
+            ApiError::PayloadTooLarge => 413,
+            ApiError::Wallet(err) => wallet_status(err),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound { .. } => "not_found",
+            ApiError::MethodNotAllowed { .. } => "method_not_allowed",
+            ApiError::UnsupportedMediaType => "unsupported_media_type",
+            ApiError::PayloadTooLarge => "payload_too_large",
+            ApiError::Wallet(err) => wallet_code(err),
+        }
+    }
+}
+
+// No catch-all arms, so a new `WalletError` variant has to pick its status
+// here before the crate builds.
+fn wallet_status(err: &WalletError) -> u16 {
+    match err {
+        WalletError::InvalidAccountNumber(_)
+        | WalletError::InvalidAmount(_)
+        | WalletError::ZeroAmount
+        | WalletError::InvalidPinFormat
+        | WalletError::InvalidDateRange { .. }
+        | WalletError::SameAccount(_) => 400,
+        WalletError::IncorrectPin { .. } | WalletError::InvalidResetToken(_) => 403,
+        WalletError::AccountNotFound(_)
+        | WalletError::StandingOrderNotFound(_)
+        | WalletError::HoldNotFound(_)
+        | WalletError::TransactionNotFound(_)
+        | WalletError::DisputeNotFound(_) => 404,
+        WalletError::DuplicateAccount(_)
+        | WalletError::AccountNotActive { .. }
+        | WalletError::InvalidStateChange { .. }
+        | WalletError::BalanceNotZero { .. }
+        | WalletError::HoldsOutstanding(_)
+        | WalletError::InvalidTierChange { .. }
+        | WalletError::PinNotSet(_)
+        | WalletError::PinAlreadySet(_)
+        | WalletError::StandingOrderNotActive(_)
+        | WalletError::EndOfDayAlreadyRun(_)
+        | WalletError::EndOfDayInFuture(_)
+        | WalletError::HoldNotActive(_)
+        | WalletError::NotReversible(_)
+        | WalletError::NotRefundable(_)
+        | WalletError::NotDisputable(_)
+        | WalletError::TransactionDisputed(_)
+        | WalletError::DisputeDecided { .. }
+        | WalletError::InvalidDisputeTransition { .. } => 409,
+        WalletError::InsufficientFunds { .. }
+        | WalletError::InsufficientCurrency { .. }
+        | WalletError::LimitExceeded { .. }
+        | WalletError::CaptureExceedsHold { .. }
+        | WalletError::RefundExceedsOriginal { .. }
+        | WalletError::NameMismatch { .. }
+        | WalletError::Overflow
+        | WalletError::Fx(_)
+        | WalletError::Payout(_)
+        | WalletError::IdempotencyConflict(_) => 422,
+        WalletError::PinLocked(_) => 423,
+        WalletError::Settlement(_)
+        | WalletError::SettlementUnknown(_)
+        | WalletError::NoConnector(_) => 502,
+        WalletError::ServiceStopped => 503,
+        WalletError::Random(_)
+        | WalletError::Fees(_)
+        | WalletError::Audit(_)
+        | WalletError::NotPersistent
+        | WalletError::OutOfOrder { .. }
+        | WalletError::Serialization(_)
+        | WalletError::Storage(_) => 500,
+    }
+}
+
+fn wallet_code(err: &WalletError) -> &'static str {
+    match err {
+        WalletError::AccountNotFound(_) => "account_not_found",
+        WalletError::DuplicateAccount(_) => "duplicate_account",
+        WalletError::InvalidAccountNumber(_) => "invalid_account_number",
+        WalletError::AccountNotActive { .. } => "account_not_active",
+        WalletError::InvalidStateChange { .. } => "invalid_state_change",
+        WalletError::BalanceNotZero { .. } => "balance_not_zero",
+        WalletError::HoldsOutstanding(_) => "holds_outstanding",
+        WalletError::InsufficientFunds { .. } => "insufficient_funds",
+        WalletError::LimitExceeded { .. } => "limit_exceeded",
+        WalletError::InvalidTierChange { .. } => "invalid_tier_change",
+        WalletError::PinNotSet(_) => "pin_not_set",
+        WalletError::PinAlreadySet(_) => "pin_already_set",
+        WalletError::IncorrectPin { .. } => "incorrect_pin",
+        WalletError::PinLocked(_) => "pin_locked",
+        WalletError::InvalidPinFormat => "invalid_pin_format",
+        WalletError::InvalidResetToken(_) => "invalid_reset_token",
+        WalletError::Random(_) => "internal_error",
+        WalletError::ZeroAmount => "zero_amount",
+        WalletError::Overflow => "overflow",
+        WalletError::SameAccount(_) => "same_account",
+        WalletError::StandingOrderNotFound(_) => "standing_order_not_found",
+        WalletError::StandingOrderNotActive(_) => "standing_order_not_active",
+        WalletError::EndOfDayAlreadyRun(_) => "end_of_day_already_run",
+        WalletError::EndOfDayInFuture(_) => "end_of_day_in_future",
+        WalletError::OutOfOrder { .. } => "record_out_of_order",
+        WalletError::InvalidDateRange { .. } => "invalid_date_range",
+        WalletError::InsufficientCurrency { .. } => "insufficient_currency",
+        WalletError::Fx(_) => "fx_error",
+        WalletError::Fees(_) => "fee_config_error",
+        WalletError::Audit(_) => "audit_error",
+        WalletError::NotPersistent => "not_persistent",
+        WalletError::Payout(_) => "payout_rejected",
+        WalletError::InvalidAmount(_) => "invalid_amount",
+        WalletError::NameMismatch { .. } => "name_mismatch",
+        WalletError::Settlement(_) => "settlement_failed",
+        WalletError::SettlementUnknown(_) => "settlement_unknown",
+        WalletError::NoConnector(_) => "no_connector",
+        WalletError::HoldNotFound(_) => "hold_not_found",
+        WalletError::HoldNotActive(_) => "hold_not_active",
+        WalletError::CaptureExceedsHold { .. } => "capture_exceeds_hold",
+        WalletError::TransactionNotFound(_) => "transaction_not_found",
+        WalletError::NotReversible(_) => "not_reversible",
+        WalletError::NotRefundable(_) => "not_refundable",
+        WalletError::RefundExceedsOriginal { .. } => "refund_exceeds_original",
+        WalletError::NotDisputable(_) => "not_disputable",
+        WalletError::TransactionDisputed(_) => "transaction_disputed",
+        WalletError::DisputeDecided { .. } => "dispute_decided",
+        WalletError::DisputeNotFound(_) => "dispute_not_found",
+        WalletError::InvalidDisputeTransition { .. } => "invalid_dispute_transition",
+        WalletError::IdempotencyConflict(_) => "idempotency_key_reused",
+        WalletError::ServiceStopped => "service_stopped",
+        WalletError::Serialization(_) => "serialization_error",
+        WalletError::Storage(_) => "storage_error",
+    }
+}
+
+fn read_json<T: DeserializeOwned>(request: &mut Request) -> Result<T, ApiError> {
+    let is_json = request.headers().iter().any(|header| {
+        header.field.equiv("Content-Type")
+            && header
+                .value
+                .as_str()
+                .to_ascii_lowercase()
+                .starts_with("application/json")
+    });
+    if !is_json {
+        return Err(ApiError::UnsupportedMediaType);
+    }
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| ApiError::BadRequest(format!("could not read the body: {err}")))?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(ApiError::PayloadTooLarge);
+    }
+    serde_json::from_slice(&body).map_err(|err| ApiError::BadRequest(err.to_string()))
+}
+
+fn idempotency_key(request: &Request) -> Result<Option<String>, ApiError> {
+    let Some(header) = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Idempotency-Key"))
+    else {
+        return Ok(None);
+    };
+    let key = header.value.as_str().trim();
+    if key.is_empty() || key.len() > 255 {
+        return Err(ApiError::BadRequest(
+            "Idempotency-Key must be 1 to 255 characters".to_string(),
+        ));
+    }
+    Ok(Some(key.to_string()))
+}
+
+fn parse_account(input: &str) -> Result<AccountNumber, ApiError> {
+    Ok(input.parse().map_err(WalletError::from)?)
+}
+
+fn positive(kobo: u64) -> Result<Money, ApiError> {
+    if kobo == 0 {
+        return Err(ApiError::BadRequest(
+            "amount must be at least 1 kobo".to_string(),
+        ));
+    }
+    Ok(Money::from_kobo(kobo))
+}
+
+fn history_query(query: &str) -> Result<HistoryQuery, ApiError> {
+    let mut offset = 0;
+    let mut limit = 20;
+    let mut from = None;
+    let mut to = None;
+    let mut kinds = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (name, value) = (percent_decode(name)?, percent_decode(value)?);
+        let name = name.as_str();
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| ApiError::BadRequest(format!("`{name}` must be a whole number")))
+        };
+        match name {
+            "offset" => offset = number()? as usize,
+            "limit" => limit = number()? as usize,
+            "from" => from = Some(number()?),
+            "to" => to = Some(number()?),
+            // Repeated, or comma-separated, kinds match any of them.
+            "kind" => {
+                for kind in value.split(',') {
+                    kinds.push(
+                        kind.parse::<TransactionKind>()
+                            .map_err(ApiError::BadRequest)?,
+                    );
+                }
+            }
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "unknown query parameter `{name}`"
+                )));
+            }
+        }
+    }
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "`limit` must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    let query = kinds
+        .into_iter()
+        .fold(HistoryQuery::new().page(offset, limit), HistoryQuery::kind);
+    Ok(match (from, to) {
+        (None, None) => query,
+        (from, to) => query.between(from.unwrap_or(0), to.unwrap_or(u64::MAX)),
+    })
+}
+
+/// Decodes `%XX` escapes and `+` in one query name or value, so a client
+/// that escapes the comma in `kind=TransferIn%2CTransferOut` is understood.
+fn percent_decode(input: &str) -> Result<String, ApiError> {
+    let invalid = || ApiError::BadRequest(format!("`{input}` is not a valid query string"));
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut rest = input.bytes();
+    while let Some(byte) = rest.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let digit = |digit: Option<u8>| {
+                    digit
+                        .and_then(|digit| char::from(digit).to_digit(16))
+                        .ok_or_else(invalid)
+                };
+                let high = digit(rest.next())?;
+                let low = digit(rest.next())?;
+                (high * 16 + low) as u8
+            }
+            byte => byte,
+        });
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn account(wallet: &Wallet, account_number: AccountNumber) -> Result<Value, WalletError> {
+    let user = wallet.user(account_number)?;
+    Ok(account_json(user, wallet.balance_of(account_number)))
+}
+
+// Account numbers are strings so their leading zeros survive; amounts are
+// whole kobo.
+fn account_json(user: &User, balance: Option<Balance>) -> Value {
+    json!({
+        "account_number": user.account_number.to_string(),
+        "name": user.name,
+        "bank": user.bank,
+        "tier": user.tier,
+        "state": user.state,
+        "opened_at": user.opened_at,
+        "balance": balance.map(balance_json),
+    })
+}
+
+fn balance_json(balance: Balance) -> Value {
+    json!({
+        "ledger": balance.ledger,
+        "available": balance.available,
+    })
+}
+
+fn receipt_json(receipt: &Receipt) -> Value {
+    json!({
+        "transaction_id": receipt.transaction_id,
+        "account_number": receipt.account_number.to_string(),
+        "kind": receipt.kind,
+        "amount": receipt.amount,
+        "fee": receipt.fee,
+        "balance_after": receipt.balance_after,
+        "timestamp": receipt.timestamp,
+    })
+}
+
+fn transaction_json(transaction: &Transaction) -> Value {
+    json!({
+        "id": transaction.id,
+        "timestamp": transaction.timestamp,
+        "kind": transaction.kind,
+        "amount": transaction.amount,
+        "fee": transaction.fee,
+        "counterparty": transaction.counterparty.map(|account| account.to_string()),
+        "related": transaction.related,
+        "narration": transaction.narration,
+        "balance_after": transaction.balance_after,
+    })
+}
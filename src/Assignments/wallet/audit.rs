@@ -0,0 +1,269 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::auth::to_hex;
+use super::storage::{Record, Storage};
+
+/// The `previous` hash of the first entry.
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One committed record, chained to the entry before it. `hash` covers the
+/// previous hash, the sequence number and the record, so changing, removing
+/// or reordering any entry breaks every hash after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub previous: String,
+    pub hash: String,
+    pub record: Record,
+}
+
+/// The write side of the audit trail. It lives beside the wallet log as
+/// `<log>.audit`; a wallet with no log still keeps the chain head, so the
+/// head can be published either way.
+#[derive(Debug)]
+pub struct AuditLog {
+    sequence: u64,
+    head: String,
+    file: Option<File>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    pub entries: u64,
+    pub head: String,
+}
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("audit line {line} is unreadable: {message}")]
+    Malformed { line: usize, message: String },
+
+    #[error("expected audit entry {expected}, found entry {found}")]
+    OutOfSequence { expected: u64, found: u64 },
+
+    #[error("audit entry {0} does not follow the entry before it")]
+    BrokenChain(u64),
+
+    #[error("audit entry {0} was altered")]
+    Altered(u64),
+
+    #[error("wallet log record {0} does not match its audit entry")]
+    RecordMismatch(u64),
+
+    #[error("wallet log has {records} records but the audit log has {entries} entries")]
+    LengthMismatch { records: u64, entries: u64 },
+
+    #[error("audit log ends at {found}, expected {expected}")]
+    HeadMismatch { expected: String, found: String },
+
+    #[error("published head {0} is not in the audit log")]
+    UnknownHead(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self {
+            sequence: 0,
+            head: GENESIS.to_string(),
+            file: None,
+        }
+    }
+
+    pub fn path_for(log_path: &Path) -> PathBuf {
+        let mut path = log_path.as_os_str().to_owned();
+        path.push(".audit");
+        PathBuf::from(path)
+    }
+
+    /// Where a record the audit log never covered is set aside for review.
+    pub fn unaudited_path_for(log_path: &Path) -> PathBuf {
+        let mut path = log_path.as_os_str().to_owned();
+        path.push(".unaudited");
+        PathBuf::from(path)
+    }
+
+    /// Opens the audit log beside the wallet log at `log_path` and checks it
+    /// against `records` the same way `verify` does.
+    ///
+    /// A crash between writing a record and auditing it leaves exactly one
+    /// record past the last entry. That record was never acknowledged, so it
+    /// is returned for the caller to set aside instead of being chained; any
+    /// more than one is an error, since a crash cannot leave them.
+    pub fn open(log_path: &Path, records: &[Record]) -> Result<(Self, Option<Record>), AuditError> {
+        let path = Self::path_for(log_path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let entries = read_entries(&mut file, true)?;
+        let head = check(&entries, records)?;
+
+        let unaudited = match records.len().checked_sub(entries.len()) {
+            Some(0) => None,
+            Some(1) => records.last().cloned(),
+            _ => {
+                return Err(AuditError::LengthMismatch {
+                    records: records.len() as u64,
+                    entries: entries.len() as u64,
+                });
+            }
+        };
+        let log = Self {
+            sequence: entries.len() as u64,
+            head,
+            file: Some(file),
+        };
+        Ok((log, unaudited))
+    }
+
+    pub fn append(&mut self, record: &Record) -> io::Result<()> {
+        let sequence = self.sequence + 1;
+        let hash = entry_hash(&self.head, sequence, record)?;
+        if let Some(file) = self.file.as_mut() {
+            let entry = AuditEntry {
+                sequence,
+                previous: self.head.clone(),
+                hash: hash.clone(),
+                record: record.clone(),
+            };
+            let mut line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+            line.push('\n');
+            file.write_all(line.as_bytes())?;
+            file.sync_data()?;
+        }
+        self.sequence = sequence;
+        self.head = hash;
+        Ok(())
+    }
+
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks the audit log beside the wallet log at `log_path`: every entry
+/// follows the one before it, every hash is what its contents give, and
+/// every record in the wallet log matches its entry. With `expected_head`,
+/// also checks nothing was cut off the end. With `published`, a head kept
+/// outside the wallet, also checks the log still runs through it, so the
+/// log cannot have been rewritten from scratch.
+pub fn verify(
+    log_path: &Path,
+    expected_head: Option<&str>,
+    published: Option<&str>,
+) -> Result<AuditReport, AuditError> {
+    let mut file = File::open(AuditLog::path_for(log_path))?;
+    let entries = read_entries(&mut file, false)?;
+    let records = Storage::read(log_path)?;
+    let head = check(&entries, &records)?;
+
+    if records.len() != entries.len() {
+        return Err(AuditError::LengthMismatch {
+            records: records.len() as u64,
+            entries: entries.len() as u64,
+        });
+    }
+    if let Some(expected) = expected_head
+        && expected != head
+    {
+        return Err(AuditError::HeadMismatch {
+            expected: expected.to_string(),
+            found: head,
+        });
+    }
+    if let Some(published) = published
+        && published != GENESIS
+        && !entries.iter().any(|entry| entry.hash == published)
+    {
+        return Err(AuditError::UnknownHead(published.to_string()));
+    }
+    Ok(AuditReport {
+        entries: entries.len() as u64,
+        head,
+    })
+}
+
+/// Appends `record` to `<log>.unaudited` for an operator to review.
+pub fn set_aside(log_path: &Path, record: &Record) -> io::Result<PathBuf> {
+    let path = AuditLog::unaudited_path_for(log_path);
+    let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
+    let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(path)
+}
+
+// Checks the chain of `entries` and each record against the entry at its
+// position, and returns the chain head. Lengths are left to the caller.
+fn check(entries: &[AuditEntry], records: &[Record]) -> Result<String, AuditError> {
+    let mut head = GENESIS.to_string();
+    for (index, entry) in entries.iter().enumerate() {
+        let expected = index as u64 + 1;
+        if entry.sequence != expected {
+            return Err(AuditError::OutOfSequence {
+                expected,
+                found: entry.sequence,
+            });
+        }
+        if entry.previous != head {
+            return Err(AuditError::BrokenChain(entry.sequence));
+        }
+        if entry_hash(&entry.previous, entry.sequence, &entry.record)? != entry.hash {
+            return Err(AuditError::Altered(entry.sequence));
+        }
+        if let Some(record) = records.get(index)
+            && serde_json::to_string(record).ok() != serde_json::to_string(&entry.record).ok()
+        {
+            return Err(AuditError::RecordMismatch(entry.sequence));
+        }
+        head = entry.hash.clone();
+    }
+    Ok(head)
+}
+
+fn entry_hash(previous: &str, sequence: u64, record: &Record) -> io::Result<String> {
+    let record = serde_json::to_string(record).map_err(io::Error::other)?;
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{previous}\n{sequence}\n{record}").as_bytes());
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// Like the wallet log, a last line without its newline was never finished;
+// it is cut off when `repair` is set and ignored otherwise.
+fn read_entries(file: &mut File, repair: bool) -> Result<Vec<AuditEntry>, AuditError> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let complete = contents.rfind('\n').map_or(0, |index| index + 1);
+    if repair && complete < contents.len() {
+        file.set_len(complete as u64)?;
+        file.sync_data()?;
+    }
+
+    contents[..complete]
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|err| AuditError::Malformed {
+                line: index + 1,
+                message: err.to_string(),
+            })
+        })
+        .collect()
+}
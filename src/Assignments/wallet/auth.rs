@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::nuban::AccountNumber;
+
+/// How PINs are checked. `rounds` is the PBKDF2-HMAC-SHA256 work factor for
+/// newly set PINs; existing PINs keep the rounds they were hashed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinPolicy {
+    pub max_attempts: u32,
+    pub rounds: u32,
+    pub reset_valid_for_secs: u64,
+}
+
+/// A salted, stretched PIN hash. Salt and hash are hex so the record log
+/// stays readable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinCredential {
+    salt: String,
+    hash: String,
+    rounds: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PinState {
+    credential: Option<PinCredential>,
+    failed_attempts: u32,
+    locked: bool,
+    reset: Option<(String, u64)>,
+}
+
+/// Per-account PIN state: the credential, consecutive failures, whether the
+/// account is locked out, and any outstanding reset token (stored hashed).
+#[derive(Debug, Clone, Default)]
+pub struct Pins {
+    by_account: HashMap<AccountNumber, PinState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinCheck {
+    NotSet,
+    Locked,
+    Correct,
+    Incorrect { failed_attempts: u32 },
+}
+
+impl Default for PinPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            rounds: 100_000,
+            reset_valid_for_secs: 15 * 60,
+        }
+    }
+}
+
+impl PinCredential {
+    pub fn new(pin: &str, rounds: u32) -> Result<Self, getrandom::Error> {
+        let mut salt = [0u8; 16];
+        getrandom::fill(&mut salt)?;
+        Ok(Self {
+            hash: to_hex(&stretch(pin, &salt, rounds)),
+            salt: to_hex(&salt),
+            rounds,
+        })
+    }
+
+    pub fn matches(&self, pin: &str) -> bool {
+        let Some(salt) = from_hex(&self.salt) else {
+            return false;
+        };
+        let candidate = to_hex(&stretch(pin, &salt, self.rounds));
+        constant_time_eq(candidate.as_bytes(), self.hash.as_bytes())
+    }
+}
+
+impl Pins {
+    pub fn new() -> Self {
+        Self {
+            by_account: HashMap::new(),
+        }
+    }
+
+    pub fn has_pin(&self, account_number: AccountNumber) -> bool {
+        self.state(account_number).credential.is_some()
+    }
+
+    pub fn check(&self, account_number: AccountNumber, pin: &str) -> PinCheck {
+        let state = self.state(account_number);
+        match &state.credential {
+            None => PinCheck::NotSet,
+            Some(_) if state.locked => PinCheck::Locked,
+            Some(credential) if credential.matches(pin) => PinCheck::Correct,
+            Some(_) => PinCheck::Incorrect {
+                failed_attempts: state.failed_attempts + 1,
+            },
+        }
+    }
+
+    pub fn failed_attempts(&self, account_number: AccountNumber) -> u32 {
+        self.state(account_number).failed_attempts
+    }
+
+    /// Whether `token` is the outstanding reset token and still valid at `now`.
+    pub fn reset_token_valid(&self, account_number: AccountNumber, token: &str, now: u64) -> bool {
+        match &self.state(account_number).reset {
+            Some((hash, expires_at)) => {
+                now < *expires_at && constant_time_eq(hash.as_bytes(), hash_token(token).as_bytes())
+            }
+            None => false,
+        }
+    }
+
+    /// Installs a new PIN, clearing failures, lockout and any reset token.
+    pub fn set(&mut self, account_number: AccountNumber, credential: PinCredential) {
+        self.by_account.insert(
+            account_number,
+            PinState {
+                credential: Some(credential),
+                ..PinState::default()
+            },
+        );
+    }
+
+    pub fn record_failure(&mut self, account_number: AccountNumber, locked: bool) {
+        let state = self.by_account.entry(account_number).or_default();
+        state.failed_attempts += 1;
+        state.locked |= locked;
+    }
+
+    pub fn record_success(&mut self, account_number: AccountNumber) {
+        if let Some(state) = self.by_account.get_mut(&account_number) {
+            state.failed_attempts = 0;
+        }
+    }
+
+    pub fn start_reset(
+        &mut self,
+        account_number: AccountNumber,
+        token_hash: String,
+        expires_at: u64,
+    ) {
+        self.by_account.entry(account_number).or_default().reset = Some((token_hash, expires_at));
+    }
+
+    fn state(&self, account_number: AccountNumber) -> PinState {
+        self.by_account
+            .get(&account_number)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// PINs are four to six digits.
+pub fn valid_pin(pin: &str) -> bool {
+    (4..=6).contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+}
+
+/// A random one-time reset token, to be delivered to the customer out of band.
+pub fn new_reset_token() -> Result<String, getrandom::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)?;
+    Ok(to_hex(&bytes))
+}
+
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn stretch(pin: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(pin.as_bytes(), salt, rounds, &mut out);
+    out
+}
+
+// Compares without returning early, so timing does not leak how much matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Calendar dates in UTC, converted to and from unix timestamps with the
+/// days-from-civil algorithm so the wallet needs no date crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+pub const SECONDS_PER_DAY: u64 = 86_400;
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Self {
+        Self { year, month, day }
+    }
+
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        Self::from_days((timestamp / SECONDS_PER_DAY) as i64)
+    }
+
+    /// Midnight UTC at the start of this date.
+    pub fn timestamp(self) -> u64 {
+        self.days().max(0) as u64 * SECONDS_PER_DAY
+    }
+
+    pub fn next_day(self) -> Self {
+        Self::from_days(self.days() + 1)
+    }
+
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        const FROM_THURSDAY: [Weekday; 7] = [
+            Weekday::Thursday,
+            Weekday::Friday,
+            Weekday::Saturday,
+            Weekday::Sunday,
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+        ];
+        FROM_THURSDAY[self.days().rem_euclid(7) as usize]
+    }
+
+    pub fn days_in_month(self) -> u32 {
+        (self.next_month_start().days() - self.month_start().days()) as u32
+    }
+
+    /// First day of this date's month.
+    pub fn month_start(self) -> Self {
+        Self::new(self.year, self.month, 1)
+    }
+
+    /// First day of the following month.
+    pub fn next_month_start(self) -> Self {
+        if self.month == 12 {
+            Self::new(self.year + 1, 1, 1)
+        } else {
+            Self::new(self.year, self.month + 1, 1)
+        }
+    }
+
+    // Days since 1970-01-01.
+    fn days(self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year as i64 - 1
+        } else {
+            self.year as i64
+        };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Self { year, month, day }
+    }
+}
+
+/// Formats as `2026-10-18`.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Parses `2026-10-18`, rejecting dates the calendar does not have.
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{input}` is not a YYYY-MM-DD date");
+        let mut parts = input.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let (year, month, day) = (next()?, next()?, next()?);
+        let digits =
+            |part: &str, len: usize| part.len() == len && part.chars().all(|c| c.is_ascii_digit());
+        if !digits(year, 4) || !digits(month, 2) || !digits(day, 2) {
+            return Err(invalid());
+        }
+        let date = Date::new(
+            year.parse().map_err(|_| invalid())?,
+            month.parse().map_err(|_| invalid())?,
+            day.parse().map_err(|_| invalid())?,
+        );
+        if !(1..=12).contains(&date.month) || date.day < 1 || date.day > date.days_in_month() {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
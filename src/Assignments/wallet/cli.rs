@@ -0,0 +1,847 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use thiserror::Error;
+
+mod demo;
+
+use super::api;
+use super::audit::AuditLog;
+use super::calendar::Date;
+use super::error::WalletError;
+use super::journal::Side;
+use super::ledger::HistoryQuery;
+use super::money::Money;
+use super::nuban::AccountNumber;
+use super::payouts::{PayoutBatch, PayoutOutcome};
+use super::service::WalletHandle;
+use super::{Bank, Receipt, Wallet};
+
+const DEFAULT_DATA_FILE: &str = "wallet.log";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+const USAGE: &str = "\
+usage: wallet [--data FILE] [--fees FILE] COMMAND
+
+Every change is appended to the --data file (default wallet.log) before it
+is applied, so state carries over between runs. Inter-bank transfers are
+charged by the schedules in the --fees file, one line per bank such as
+`Kuda flat=10 percentage_bps=50 cap=50 free_per_month=2`; without one they
+are free. Amounts are in naira, like 2500 or 2,500.50; account numbers are
+the 10-digit NUBAN.
+
+commands:
+  open NAME BANK                         open an account at Opay, PalmPay, Kuda or Moniepoint
+  set-pin ACCOUNT                        set an account's 4 to 6 digit transaction PIN
+  deposit ACCOUNT AMOUNT                 credit an account
+  withdraw ACCOUNT AMOUNT                debit an account
+  transfer FROM TO AMOUNT                move money between accounts
+  hold ACCOUNT AMOUNT MINUTES            reserve funds for up to MINUTES without debiting them
+  capture HOLD AMOUNT                    debit up to the held amount and release the rest
+  void HOLD                              release a hold without debiting anything
+  freeze ACCOUNT REASON                  stop all debits on an account
+  unfreeze ACCOUNT REASON                lift a freeze
+  reactivate ACCOUNT REASON              bring a dormant account back into use
+  close ACCOUNT REASON [SWEEP_TO]        close for good, moving any balance to SWEEP_TO
+  states ACCOUNT                         every state change on an account
+  balance ACCOUNT                        ledger and available balance
+  history ACCOUNT [LIMIT]                latest transactions, newest first
+  statement ACCOUNT FROM TO [FORMAT]     statement for YYYY-MM-DD dates, as text, csv or json
+  accounts                               every account and its balance
+  journal [LIMIT]                        latest double-entry journal entries, newest first
+  audit [HEAD]                           verify the audit log, and that it still runs through HEAD
+  payout-check FUNDING FILE [--batch ID]  validate a payout CSV without paying anything
+  payout FUNDING FILE [--batch ID]        pay every row of a payout CSV out of FUNDING
+  repl                                   read commands interactively (the default)
+  serve [ADDRESS]                        serve the JSON API (default 127.0.0.1:8080)
+  demo                                   run the walkthrough of every feature on a scratch log
+  help                                   show this message
+
+Commands that debit an account, and set-pin, ask for the PIN and read it
+from the next line of input, so it never lands in shell or REPL history.
+
+In the REPL, names with spaces go in double quotes, TAB completes commands,
+banks and account numbers, and `quit` leaves.
+
+A payout file has account_number,amount,narration rows and names its batch
+with a `# batch: ID` line, or --batch gives the id. Every row is checked
+before anything is paid; rows that fail while paying are written to
+FILE.failed.csv, which can be run on its own. Running a batch again never
+pays a row twice.";
+
+const COMMANDS: [&str; 24] = [
+    "open",
+    "set-pin",
+    "deposit",
+    "withdraw",
+    "transfer",
+    "hold",
+    "capture",
+    "void",
+    "freeze",
+    "unfreeze",
+    "reactivate",
+    "close",
+    "states",
+    "balance",
+    "history",
+    "statement",
+    "accounts",
+    "journal",
+    "audit",
+    "payout-check",
+    "payout",
+    "help",
+    "quit",
+    "exit",
+];
+
+const FORMATS: [&str; 3] = ["text", "csv", "json"];
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("{0}\nrun `help` for usage")]
+    Usage(String),
+
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+
+    #[error("terminal: {0}")]
+    Readline(#[from] ReadlineError),
+
+    #[error("{path}: {source}")]
+    File { path: PathBuf, source: io::Error },
+}
+
+/// One wallet operation, parsed from a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Open {
+        name: String,
+        bank: Bank,
+    },
+    SetPin(AccountNumber),
+    Deposit {
+        account_number: AccountNumber,
+        amount: Money,
+    },
+    Withdraw {
+        account_number: AccountNumber,
+        amount: Money,
+    },
+    Transfer {
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+    },
+    Hold {
+        account_number: AccountNumber,
+        amount: Money,
+        minutes: u64,
+    },
+    Capture {
+        hold_id: u64,
+        amount: Money,
+    },
+    Void(u64),
+    Freeze {
+        account_number: AccountNumber,
+        reason: String,
+    },
+    Unfreeze {
+        account_number: AccountNumber,
+        reason: String,
+    },
+    Reactivate {
+        account_number: AccountNumber,
+        reason: String,
+    },
+    Close {
+        account_number: AccountNumber,
+        reason: String,
+        sweep_to: Option<AccountNumber>,
+    },
+    States(AccountNumber),
+    Balance(AccountNumber),
+    History {
+        account_number: AccountNumber,
+        limit: usize,
+    },
+    Statement {
+        account_number: AccountNumber,
+        from: Date,
+        to: Date,
+        format: StatementFormat,
+    },
+    Accounts,
+    Journal(usize),
+    Audit(Option<String>),
+    PayoutCheck {
+        funding: AccountNumber,
+        file: PathBuf,
+        batch: Option<String>,
+    },
+    Payout {
+        funding: AccountNumber,
+        file: PathBuf,
+        batch: Option<String>,
+    },
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+/// Tab completion for the REPL: command names first, then banks, statement
+/// formats and the account numbers the wallet knows about.
+#[derive(Debug, Default)]
+struct WalletHelper {
+    accounts: Vec<String>,
+}
+
+/// Runs the command-line tool over `args`, which do not include the
+/// program's own name.
+pub fn run(mut args: &[String]) -> Result<(), CliError> {
+    let mut data_file = PathBuf::from(DEFAULT_DATA_FILE);
+    let mut fee_file = None;
+    loop {
+        match args {
+            [flag, path, ..] if flag == "--data" => data_file = PathBuf::from(path),
+            [flag, path, ..] if flag == "--fees" => fee_file = Some(PathBuf::from(path)),
+            [flag] if flag == "--data" || flag == "--fees" => {
+                return Err(CliError::Usage(format!("{flag} needs a file")));
+            }
+            _ => break,
+        }
+        args = &args[2..];
+    }
+    let open = || -> Result<Wallet, CliError> {
+        let mut wallet = Wallet::open(&data_file)?;
+        if wallet.unaudited().is_some() {
+            eprintln!(
+                "warning: the last record in {} was never audited; it was moved to {} for review",
+                data_file.display(),
+                AuditLog::unaudited_path_for(&data_file).display()
+            );
+        }
+        if let Some(fee_file) = &fee_file {
+            wallet.load_fees(fee_file)?;
+        }
+        Ok(wallet)
+    };
+
+    match args.first().map(String::as_str) {
+        None | Some("repl") => repl(open()?),
+        Some("serve") => {
+            let address = args.get(1).map_or(DEFAULT_ADDRESS, String::as_str);
+            let (handle, _worker) = WalletHandle::spawn(open()?);
+            println!("Wallet API listening on http://{address}");
+            api::serve(handle, address).map_err(WalletError::from)?;
+            Ok(())
+        }
+        Some("demo") => Ok(demo::run()?),
+        Some(_) => {
+            let command = Command::parse(args)?;
+            let mut wallet = open()?;
+            print!("{}", execute(&mut wallet, &command, &mut prompt_pin)?);
+            Ok(())
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(words: &[String]) -> Result<Self, CliError> {
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let command = match words.as_slice() {
+            ["open", name, bank] => Command::Open {
+                name: name.trim().to_string(),
+                bank: bank.parse().map_err(CliError::Usage)?,
+            },
+            ["set-pin", account] => Command::SetPin(account_number(account)?),
+            ["deposit", account, amount] => Command::Deposit {
+                account_number: account_number(account)?,
+                amount: money(amount)?,
+            },
+            ["withdraw", account, amount] => Command::Withdraw {
+                account_number: account_number(account)?,
+                amount: money(amount)?,
+            },
+            ["transfer", from, to, amount] => Command::Transfer {
+                from: account_number(from)?,
+                to: account_number(to)?,
+                amount: money(amount)?,
+            },
+            ["hold", account, amount, minutes] => Command::Hold {
+                account_number: account_number(account)?,
+                amount: money(amount)?,
+                minutes: positive(minutes)?,
+            },
+            ["capture", hold, amount] => Command::Capture {
+                hold_id: positive(hold)?,
+                amount: money(amount)?,
+            },
+            ["void", hold] => Command::Void(positive(hold)?),
+            ["freeze", account, reason] => Command::Freeze {
+                account_number: account_number(account)?,
+                reason: reason.trim().to_string(),
+            },
+            ["unfreeze", account, reason] => Command::Unfreeze {
+                account_number: account_number(account)?,
+                reason: reason.trim().to_string(),
+            },
+            ["reactivate", account, reason] => Command::Reactivate {
+                account_number: account_number(account)?,
+                reason: reason.trim().to_string(),
+            },
+            ["close", account, reason, rest @ ..] if rest.len() <= 1 => Command::Close {
+                account_number: account_number(account)?,
+                reason: reason.trim().to_string(),
+                sweep_to: rest
+                    .first()
+                    .map(|sweep_to| account_number(sweep_to))
+                    .transpose()?,
+            },
+            ["states", account] => Command::States(account_number(account)?),
+            ["balance", account] => Command::Balance(account_number(account)?),
+            ["history", account, rest @ ..] if rest.len() <= 1 => Command::History {
+                account_number: account_number(account)?,
+                limit: match rest {
+                    [limit] => limit_of(limit)?,
+                    _ => DEFAULT_HISTORY_LIMIT,
+                },
+            },
+            ["statement", account, from, to, rest @ ..] if rest.len() <= 1 => Command::Statement {
+                account_number: account_number(account)?,
+                from: from.parse().map_err(CliError::Usage)?,
+                to: to.parse().map_err(CliError::Usage)?,
+                format: match rest {
+                    [format] => format.parse().map_err(CliError::Usage)?,
+                    _ => StatementFormat::Text,
+                },
+            },
+            ["accounts"] => Command::Accounts,
+            ["journal", rest @ ..] if rest.len() <= 1 => Command::Journal(match rest {
+                [limit] => limit_of(limit)?,
+                _ => DEFAULT_HISTORY_LIMIT,
+            }),
+            ["audit"] => Command::Audit(None),
+            ["audit", head] => Command::Audit(Some(head.to_string())),
+            ["payout-check", funding, file, rest @ ..] if batch_flag(rest).is_some() => {
+                Command::PayoutCheck {
+                    funding: account_number(funding)?,
+                    file: PathBuf::from(file),
+                    batch: batch_flag(rest).flatten(),
+                }
+            }
+            ["payout", funding, file, rest @ ..] if batch_flag(rest).is_some() => Command::Payout {
+                funding: account_number(funding)?,
+                file: PathBuf::from(file),
+                batch: batch_flag(rest).flatten(),
+            },
+            ["help"] => Command::Help,
+            [name, ..] if COMMANDS.contains(name) => {
+                return Err(CliError::Usage(format!("wrong arguments for `{name}`")));
+            }
+            [name, ..] => return Err(CliError::Usage(format!("unknown command `{name}`"))),
+            [] => return Err(CliError::Usage("no command given".to_string())),
+        };
+        if let Command::Open { name, .. } = &command
+            && name.is_empty()
+        {
+            return Err(CliError::Usage("the account name is empty".to_string()));
+        }
+        if let Command::Freeze { reason, .. }
+        | Command::Unfreeze { reason, .. }
+        | Command::Reactivate { reason, .. }
+        | Command::Close { reason, .. } = &command
+            && reason.is_empty()
+        {
+            return Err(CliError::Usage("the reason is empty".to_string()));
+        }
+        Ok(command)
+    }
+}
+
+impl std::str::FromStr for StatementFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "text" => Ok(StatementFormat::Text),
+            "csv" => Ok(StatementFormat::Csv),
+            "json" => Ok(StatementFormat::Json),
+            _ => Err(format!("unknown statement format `{input}`")),
+        }
+    }
+}
+
+/// Carries out `command` and returns what to print. `read_pin` is called
+/// with a prompt whenever the command needs a PIN.
+pub fn execute(
+    wallet: &mut Wallet,
+    command: &Command,
+    read_pin: &mut dyn FnMut(&str) -> io::Result<String>,
+) -> Result<String, CliError> {
+    let mut pin_for = |account_number: &AccountNumber| -> Result<String, CliError> {
+        Ok(read_pin(&format!("PIN for {account_number}")).map_err(ReadlineError::from)?)
+    };
+    let mut out = String::new();
+    match command {
+        Command::Open { name, bank } => {
+            let account_number = wallet.open_account(name, *bank)?;
+            let _ = writeln!(out, "Opened {account_number} for {name} at {bank:?}");
+        }
+        Command::SetPin(account_number) => {
+            wallet.set_pin(*account_number, &pin_for(account_number)?)?;
+            let _ = writeln!(out, "PIN set for {account_number}");
+        }
+        Command::Deposit {
+            account_number,
+            amount,
+        } => out = receipt_line(&wallet.deposit_to(*account_number, *amount, None)?),
+        Command::Withdraw {
+            account_number,
+            amount,
+        } => {
+            let pin = pin_for(account_number)?;
+            out = receipt_line(&wallet.withdraw_from(*account_number, *amount, &pin, None)?);
+        }
+        Command::Transfer { from, to, amount } => {
+            let pin = pin_for(from)?;
+            out = receipt_line(&wallet.transfer(*from, *to, *amount, &pin, None)?);
+        }
+        Command::Hold {
+            account_number,
+            amount,
+            minutes,
+        } => {
+            let pin = pin_for(account_number)?;
+            let expires_in = Duration::from_secs(minutes.saturating_mul(60));
+            let hold = wallet.authorize_hold(*account_number, *amount, expires_in, &pin, None)?;
+            let _ = writeln!(
+                out,
+                "Hold {} of {} on {} for {} minutes",
+                hold.id, hold.amount, hold.account_number, minutes
+            );
+        }
+        Command::Capture { hold_id, amount } => {
+            out = receipt_line(&wallet.capture_hold(*hold_id, *amount, None)?);
+        }
+        Command::Void(hold_id) => {
+            wallet.void_hold(*hold_id)?;
+            let _ = writeln!(out, "Hold {hold_id} voided");
+        }
+        Command::Freeze {
+            account_number,
+            reason,
+        } => {
+            wallet.freeze(*account_number, reason)?;
+            let _ = writeln!(out, "{account_number} is frozen");
+        }
+        Command::Unfreeze {
+            account_number,
+            reason,
+        } => {
+            wallet.unfreeze(*account_number, reason)?;
+            let _ = writeln!(out, "{account_number} is active again");
+        }
+        Command::Reactivate {
+            account_number,
+            reason,
+        } => {
+            wallet.reactivate(*account_number, reason)?;
+            let _ = writeln!(out, "{account_number} is active again");
+        }
+        Command::Close {
+            account_number,
+            reason,
+            sweep_to,
+        } => {
+            wallet.close_account(*account_number, *sweep_to, reason)?;
+            let _ = match sweep_to {
+                Some(sweep_to) => {
+                    writeln!(out, "Closed {account_number}, balance swept to {sweep_to}")
+                }
+                None => writeln!(out, "Closed {account_number}"),
+            };
+        }
+        Command::States(account_number) => {
+            wallet.user(*account_number)?;
+            for change in wallet.state_history(*account_number) {
+                let _ = writeln!(
+                    out,
+                    "{}  {:?} -> {:?}  {}",
+                    Date::from_timestamp(change.at),
+                    change.from,
+                    change.to,
+                    change.reason
+                );
+            }
+        }
+        Command::Balance(account_number) => {
+            let user = wallet.user(*account_number)?;
+            let balance = wallet
+                .balance_of(*account_number)
+                .ok_or(WalletError::AccountNotFound(*account_number))?;
+            let _ = writeln!(
+                out,
+                "{} {} ({:?}, {:?}): ledger {}, available {}",
+                account_number, user.name, user.bank, user.state, balance.ledger, balance.available
+            );
+        }
+        Command::History {
+            account_number,
+            limit,
+        } => {
+            let page = wallet
+                .history(*account_number, &HistoryQuery::new().page(0, *limit))
+                .ok_or(WalletError::AccountNotFound(*account_number))?;
+            let _ = writeln!(
+                out,
+                "{:>6}  {:<10}  {:<17}  {:>14}  {:>10}  {:>14}",
+                "Ref", "Date", "Kind", "Amount", "Fee", "Balance"
+            );
+            for transaction in &page.items {
+                let _ = writeln!(
+                    out,
+                    "{:>6}  {:<10}  {:<17}  {:>14}  {:>10}  {:>14}",
+                    transaction.id,
+                    Date::from_timestamp(transaction.timestamp),
+                    format!("{:?}", transaction.kind),
+                    transaction.amount,
+                    transaction.fee,
+                    transaction.balance_after
+                );
+            }
+            let _ = writeln!(out, "{} of {} transactions", page.items.len(), page.total);
+        }
+        Command::Statement {
+            account_number,
+            from,
+            to,
+            format,
+        } => {
+            let statement = wallet.statement(*account_number, *from, *to)?;
+            out = match format {
+                StatementFormat::Text => statement.to_text(),
+                StatementFormat::Csv => statement.to_csv(),
+                StatementFormat::Json => {
+                    let mut json = statement.to_json().map_err(WalletError::from)?;
+                    json.push('\n');
+                    json
+                }
+            };
+        }
+        Command::Accounts => {
+            for user in wallet.accounts() {
+                let balance = wallet
+                    .balance_of(user.account_number)
+                    .map_or(Money::ZERO, |balance| balance.ledger);
+                let _ = writeln!(
+                    out,
+                    "{}  {:<24}  {:<10}  {:<8}  {:>14}",
+                    user.account_number,
+                    user.name,
+                    format!("{:?}", user.bank),
+                    format!("{:?}", user.state),
+                    balance
+                );
+            }
+        }
+        Command::Journal(limit) => {
+            for entry in wallet.journal_entries().iter().rev().take(*limit) {
+                let _ = writeln!(
+                    out,
+                    "#{}  {}  {}",
+                    entry.id,
+                    Date::from_timestamp(entry.timestamp),
+                    entry.memo
+                );
+                for posting in &entry.postings {
+                    let (debit, credit) = match posting.side {
+                        Side::Debit => (posting.amount.to_string(), String::new()),
+                        Side::Credit => (String::new(), posting.amount.to_string()),
+                    };
+                    let _ = writeln!(
+                        out,
+                        "    {:<32}  {:>14}  {:>14}",
+                        posting.account, debit, credit
+                    );
+                }
+            }
+        }
+        Command::Audit(head) => {
+            let report = wallet.verify_audit(head.as_deref())?;
+            let _ = writeln!(out, "{} entries, head {}", report.entries, report.head);
+        }
+        Command::PayoutCheck {
+            funding,
+            file,
+            batch,
+        } => {
+            let batch = read_batch(*funding, file, batch.as_deref())?;
+            let plan = wallet.validate_payouts(&batch)?;
+            let _ = writeln!(
+                out,
+                "{}: {} rows to pay, {} plus {} in fees, {} from {}",
+                batch.id, plan.rows, plan.amount, plan.fees, plan.total_debit, funding
+            );
+        }
+        Command::Payout {
+            funding,
+            file,
+            batch,
+        } => {
+            let batch = read_batch(*funding, file, batch.as_deref())?;
+            let report = wallet.run_payouts(&batch, &pin_for(funding)?)?;
+            for result in &report.results {
+                let row = &result.row;
+                let _ = match &result.outcome {
+                    PayoutOutcome::Paid(receipt) => writeln!(
+                        out,
+                        "line {:>4}  {}  {:>14}  paid #{}",
+                        row.line, row.account_number, row.amount, receipt.transaction_id
+                    ),
+                    PayoutOutcome::Failed(err) => writeln!(
+                        out,
+                        "line {:>4}  {}  {:>14}  failed: {}",
+                        row.line, row.account_number, row.amount, err
+                    ),
+                };
+            }
+            let _ = writeln!(out, "{}", report.summary());
+            if let Some(retry) = report.retry_batch() {
+                let path = failed_path(file);
+                fs::write(&path, retry.to_csv()).map_err(|source| CliError::File {
+                    path: path.clone(),
+                    source,
+                })?;
+                let _ = writeln!(
+                    out,
+                    "Failed rows written to {}; run `payout {} {}` to retry them",
+                    path.display(),
+                    funding,
+                    path.display()
+                );
+            }
+        }
+        Command::Help => {
+            out.push_str(USAGE);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn repl(mut wallet: Wallet) -> Result<(), CliError> {
+    let mut editor: Editor<WalletHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(WalletHelper::default()));
+    println!("Wallet REPL. Type `help` for commands, TAB to complete, `quit` to leave.");
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.accounts = wallet
+                .accounts()
+                .iter()
+                .map(|user| user.account_number.to_string())
+                .collect();
+        }
+        let line = match editor.readline("wallet> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let words = match split_words(&line) {
+            Ok(words) if words.is_empty() => continue,
+            Ok(words) => words,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
+            }
+        };
+        if matches!(words[0].as_str(), "quit" | "exit") {
+            return Ok(());
+        }
+
+        let _ = editor.add_history_entry(line.as_str());
+        let result = Command::parse(&words)
+            .and_then(|command| execute(&mut wallet, &command, &mut prompt_pin));
+        match result {
+            Ok(out) => print!("{out}"),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+/// Asks for a PIN on stderr and reads it from the next line of stdin, so it
+/// can be typed or piped in but never appears on a command line.
+fn prompt_pin(prompt: &str) -> io::Result<String> {
+    eprint!("{prompt}: ");
+    io::Write::flush(&mut io::stderr())?;
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no PIN was entered",
+        ));
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Splits a REPL line on whitespace, keeping "double quoted" text together.
+fn split_words(line: &str) -> Result<Vec<String>, CliError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quoted {
+        return Err(CliError::Usage("unclosed quote".to_string()));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn read_batch(
+    funding: AccountNumber,
+    file: &Path,
+    batch: Option<&str>,
+) -> Result<PayoutBatch, CliError> {
+    let csv = fs::read_to_string(file).map_err(|source| CliError::File {
+        path: file.to_path_buf(),
+        source,
+    })?;
+    Ok(PayoutBatch::parse(batch, funding, &csv).map_err(WalletError::from)?)
+}
+
+// The optional `--batch ID` after a payout command's arguments: None when
+// the words are anything else.
+fn batch_flag(words: &[&str]) -> Option<Option<String>> {
+    match words {
+        [] => Some(None),
+        ["--batch", id] => Some(Some(id.to_string())),
+        _ => None,
+    }
+}
+
+fn failed_path(file: &Path) -> PathBuf {
+    let mut name = file.file_stem().unwrap_or_default().to_os_string();
+    name.push(".failed.csv");
+    file.with_file_name(name)
+}
+
+fn account_number(input: &str) -> Result<AccountNumber, CliError> {
+    Ok(input.parse().map_err(WalletError::from)?)
+}
+
+fn money(input: &str) -> Result<Money, CliError> {
+    let amount: Money = input.parse().map_err(WalletError::from)?;
+    if amount.is_zero() {
+        return Err(CliError::Usage(
+            "the amount must be more than zero".to_string(),
+        ));
+    }
+    Ok(amount)
+}
+
+fn limit_of(input: &str) -> Result<usize, CliError> {
+    Ok(usize::try_from(positive(input)?).unwrap_or(usize::MAX))
+}
+
+fn positive(input: &str) -> Result<u64, CliError> {
+    input
+        .parse()
+        .ok()
+        .filter(|number| *number > 0)
+        .ok_or_else(|| CliError::Usage(format!("`{input}` is not a positive number")))
+}
+
+fn receipt_line(receipt: &Receipt) -> String {
+    format!(
+        "#{} {:?} of {} on {} (fee {}), balance now {}\n",
+        receipt.transaction_id,
+        receipt.kind,
+        receipt.amount,
+        receipt.account_number,
+        receipt.fee,
+        receipt.balance_after
+    )
+}
+
+impl Completer for WalletHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(char::is_whitespace)
+            .map_or(0, |index| index + 1);
+        let prefix = &line[start..pos];
+        let banks = Bank::ALL.map(|bank| format!("{bank:?}"));
+        let options: Vec<&str> = if line[..start].trim().is_empty() {
+            COMMANDS.to_vec()
+        } else {
+            self.accounts
+                .iter()
+                .map(String::as_str)
+                .chain(banks.iter().map(String::as_str))
+                .chain(FORMATS)
+                .collect()
+        };
+        let matches = options
+            .into_iter()
+            .filter(|option| option.to_lowercase().starts_with(&prefix.to_lowercase()))
+            .map(|option| format!("{option} "))
+            .collect();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for WalletHelper {
+    type Hint = String;
+}
+
+impl Highlighter for WalletHelper {}
+
+impl Validator for WalletHelper {}
+
+impl Helper for WalletHelper {}
@@ -0,0 +1,470 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::super::adjustments::DisputeState;
+use super::super::audit::{self, AuditLog};
+use super::super::calendar::{Date, SECONDS_PER_DAY, Weekday};
+use super::super::clock::ManualClock;
+use super::super::connector::{MockSwitch, SwitchConfig};
+use super::super::error::WalletError;
+use super::super::fx::{Amount, Currency};
+use super::super::kyc::KycTier;
+use super::super::ledger::{HistoryQuery, TransactionKind, now};
+use super::super::money::Money;
+use super::super::nuban::AccountNumber;
+use super::super::payouts::PayoutBatch;
+use super::super::savings::SavingsProduct;
+use super::super::standing_orders::{Frequency, RunOutcome};
+use super::super::{Bank, User, Wallet};
+
+/// A walkthrough of every feature against `wallet-demo.log` in the temp
+/// directory, so it never touches the CLI's own `wallet.log`.
+pub fn run() -> Result<(), WalletError> {
+    let log_path = std::env::temp_dir().join("wallet-demo.log");
+    println!("Demo wallet log: {}", log_path.display());
+    let mut wallet = Wallet::open(&log_path)?;
+
+    // Opay transfers settle through a mock switch that is slow and sometimes
+    // times out, so the requery path gets exercised.
+    let switch = MockSwitch::new(SwitchConfig {
+        latency: Duration::from_millis(20),
+        timeout_per_mille: 300,
+        ..SwitchConfig::default()
+    });
+    wallet.add_connector(Box::new(switch.connector(Bank::Opay)));
+    wallet.load_fees("fees.txt")?;
+
+    // Only the first run opens the accounts; later runs pick them up from disk.
+    let (uche, ada) = match wallet.accounts().as_slice() {
+        [first, second, ..] => (first.account_number, second.account_number),
+        _ => {
+            let uche = wallet.open_account("Uche", Bank::Kuda)?;
+            let ada = wallet.open_account("Ada", Bank::Opay)?;
+            wallet.set_pin(uche, "1234")?;
+            wallet.set_pin(ada, "5678")?;
+            wallet.deposit_to(uche, Money::naira(5_000), None)?;
+            wallet.deposit_to(ada, "8500.50".parse()?, None)?;
+            (uche, ada)
+        }
+    };
+    switch.register_account(Bank::Opay, ada, "Ada");
+    println!("Uche is {} at Kuda, Ada is {} at Opay", uche, ada);
+    // Kept as if published elsewhere, so the log can be checked against it
+    // once more has been written.
+    let published_head = wallet.audit_head().to_string();
+
+    // A customer gets one digit of Ada's number wrong.
+    let mut typed = ada.to_string().into_bytes();
+    typed[8] = b'0' + (typed[8] - b'0' + 1) % 10;
+    let typed = String::from_utf8(typed).expect("account numbers are ASCII digits");
+    match wallet.resolve_account(&typed, Bank::Opay) {
+        Ok(account_number) => println!("{} resolved to {}", typed, account_number),
+        Err(err) => println!("Mistyped account number: {}", err),
+    }
+    let ada = wallet.resolve_account(&ada.to_string(), Bank::Opay)?;
+
+    wallet.set_idempotency_retention(Duration::from_secs(60 * 60));
+    let deposit_key = format!("deposit-{}", now());
+    let deposit = wallet.deposit_to(uche, Money::naira(4_000), Some(&deposit_key))?;
+    let retried = wallet.deposit_to(uche, Money::naira(4_000), Some(&deposit_key))?;
+    println!(
+        "Retried deposit returned transaction {} again",
+        retried.transaction_id
+    );
+    if let Err(err) = wallet.deposit_to(uche, Money::naira(40_000), Some(&deposit_key)) {
+        println!("Same key, different amount: {}", err);
+    }
+    let preview = wallet.preview_transfer_fee(uche, ada, Money::naira(2_500))?;
+    println!(
+        "Transfer fee: {} ({} free transfers left this month)",
+        preview.fee, preview.free_transfers_left
+    );
+    let withdraw = wallet.withdraw_from(ada, Money::naira(7_000), "5678", None);
+    let transfer = wallet.transfer(uche, ada, Money::naira(2_500), "1234", None);
+
+    println!("Deposit: {:?}", deposit);
+    match withdraw {
+        Ok(receipt) => println!("Withdraw: {:?}", receipt),
+        Err(err) => println!("Withdraw failed: {}", err),
+    }
+    match &transfer {
+        Ok(receipt) => println!("Transfer: {:?}", receipt),
+        Err(err) => println!("Transfer failed: {}", err),
+    }
+    match wallet.withdraw_from(uche, Money::naira(60_000), "1234", None) {
+        Ok(receipt) => println!("Large withdrawal: {:?}", receipt),
+        Err(err) => println!("Large withdrawal refused: {}", err),
+    }
+    // Later runs find Uche already upgraded, which is refused.
+    match wallet.upgrade_tier(uche, KycTier::Tier2) {
+        Ok(()) => println!(
+            "Uche is now Tier 2, sending up to {} at a time",
+            KycTier::Tier2.limits().single_transaction
+        ),
+        Err(err) => println!("Tier upgrade refused: {}", err),
+    }
+
+    let hold = wallet.authorize_hold(
+        uche,
+        Money::naira(1_000),
+        Duration::from_secs(15 * 60),
+        "1234",
+        None,
+    )?;
+    println!("Hold placed: {:?}", hold);
+    print_balance(&wallet, uche);
+    let capture = wallet.capture_hold(hold.id, Money::naira(750), None)?;
+    println!("Captured: {:?}", capture);
+    wallet.expire_holds()?;
+
+    match wallet.withdraw_from(ada, Money::naira(100), "0000", None) {
+        Ok(receipt) => println!("Withdrawal with wrong PIN went through: {:?}", receipt),
+        Err(err) => println!("Wrong PIN: {}", err),
+    }
+    let token = wallet.request_pin_reset(ada)?;
+    wallet.complete_pin_reset(ada, &token, "5678")?;
+    println!("PIN for {} reset", ada);
+
+    match wallet.add_user(User::new("Impostor".to_string(), Bank::Kuda, uche)) {
+        Ok(()) => println!("Duplicate account was accepted"),
+        Err(err) => println!("Duplicate account refused: {}", err),
+    }
+    wallet.freeze(ada, "fraud investigation")?;
+    if let Err(err) = wallet.withdraw_from(ada, Money::naira(100), "5678", None) {
+        println!("Frozen account: {}", err);
+    }
+    wallet.unfreeze(ada, "investigation closed")?;
+    for change in wallet.state_history(ada) {
+        println!("{} state change: {:?}", ada, change);
+    }
+    wallet.set_dormant_after(Duration::from_secs(180 * SECONDS_PER_DAY));
+    println!("Dormant accounts: {:?}", wallet.mark_dormant_accounts()?);
+
+    let refund = wallet.refund(capture.transaction_id, Money::naira(250), None)?;
+    println!("Refunded: {:?}", refund);
+    let dispute = wallet.open_dispute(capture.transaction_id)?;
+    wallet.move_dispute(dispute.id, DisputeState::UnderReview)?;
+    let dispute = wallet.move_dispute(dispute.id, DisputeState::Won)?;
+    println!("Dispute: {:?}", dispute);
+    if let Ok(receipt) = &transfer {
+        match wallet.reverse(receipt.transaction_id, None) {
+            Ok(reversal) => println!("Reversed transfer: {:?}", reversal),
+            Err(err) => println!("Reversal failed: {}", err),
+        }
+    }
+
+    for account_number in [uche, ada] {
+        print_balance(&wallet, account_number);
+    }
+
+    let recent = HistoryQuery::new().page(0, 10);
+    if let Some(page) = wallet.history(uche, &recent) {
+        for entry in page.items {
+            println!("{} history: {:?}", uche, entry);
+        }
+    }
+    let withdrawals = HistoryQuery::new().kind(TransactionKind::Withdrawal);
+    println!(
+        "{} withdrawals: {:?}",
+        ada,
+        wallet.history(ada, &withdrawals)
+    );
+    println!("Transaction 1: {:?}", wallet.transaction(1));
+
+    let today = Date::from_timestamp(now());
+    let yesterday = Date::from_timestamp(now() - SECONDS_PER_DAY);
+    let savers: Vec<AccountNumber> = wallet
+        .accounts()
+        .iter()
+        .filter(|user| user.savings.is_some())
+        .map(|user| user.account_number)
+        .collect();
+    let (savings, fixed) = match savers.as_slice() {
+        [savings, fixed, ..] => (*savings, *fixed),
+        _ => {
+            let compound = SavingsProduct::new(1_200).compound();
+            let savings = wallet.open_savings_account("Uche", Bank::Kuda, compound)?;
+            let fixed_term = SavingsProduct::new(1_800).fixed_term(90);
+            let fixed = wallet.open_savings_account("Ada", Bank::Opay, fixed_term)?;
+            wallet.set_pin(savings, "1234")?;
+            wallet.set_pin(fixed, "5678")?;
+            wallet.deposit_to(savings, Money::naira(40_000), None)?;
+            wallet.deposit_to(fixed, Money::naira(30_000), None)?;
+            (savings, fixed)
+        }
+    };
+    match wallet.run_end_of_day(yesterday) {
+        Ok(paid) => println!("End of day {}: {} interest payments", yesterday, paid.len()),
+        Err(err) => println!("End of day not run: {}", err),
+    }
+    for account_number in [savings, fixed] {
+        if let Some(account) = wallet.savings(account_number) {
+            println!(
+                "{} has accrued {} this month",
+                account_number,
+                account.accrued_interest()
+            );
+        }
+    }
+    match wallet.withdraw_from(fixed, Money::naira(5_000), "5678", None) {
+        Ok(receipt) => println!("Withdrawal from fixed term: {:?}", receipt),
+        Err(err) => println!("Withdrawal from fixed term refused: {}", err),
+    }
+
+    let statement = wallet.statement(uche, today.month_start(), today)?;
+    print!("{}", statement.to_text());
+    print!("{}", statement.to_csv());
+    println!("{}", statement.to_json().map_err(io::Error::from)?);
+
+    // A remittance lands in dollars and part of it is changed to naira.
+    wallet.load_rates("fx_rates.txt")?;
+    let remittance = Amount::new(Currency::Usd, "50.00".parse()?);
+    println!(
+        "Received {}, USD balance now {}",
+        remittance,
+        wallet.deposit_currency(uche, remittance, None)?
+    );
+    match wallet.convert(
+        uche,
+        Currency::Usd,
+        Currency::Ngn,
+        "20.00".parse()?,
+        "1234",
+        None,
+    ) {
+        Ok(quote) => println!(
+            "Converted {} to {} (mid {}, spread {})",
+            quote.sold, quote.bought, quote.mid, quote.spread
+        ),
+        Err(err) => println!("Conversion failed: {}", err),
+    }
+    match wallet.convert(
+        uche,
+        Currency::Ngn,
+        Currency::Gbp,
+        "1000.00".parse()?,
+        "1234",
+        None,
+    ) {
+        Ok(quote) => println!("Converted {} to {}", quote.sold, quote.bought),
+        Err(err) => println!("Conversion failed: {}", err),
+    }
+    for balance in wallet.balances(uche)? {
+        println!("{} holds {}", uche, balance);
+    }
+
+    // A small payroll out of Uche's account. Opay rows settle through the
+    // flaky switch; any row it cannot settle is retried on its own. On later
+    // runs every row is found already paid.
+    let payroll = format!(
+        "account_number,amount,narration\n\
+         {ada},1500,October salary\n\
+         {ada},250.50,\"Transport, October\"\n\
+         {ada},100,Airtime\n"
+    );
+    let payroll = PayoutBatch::parse(Some("payroll-2026-10"), uche, &payroll)?;
+    match wallet.validate_payouts(&payroll) {
+        Ok(plan) => println!(
+            "Payroll checks out: {} rows, {} plus {} in fees",
+            plan.rows, plan.amount, plan.fees
+        ),
+        Err(err) => println!("Payroll rejected: {}", err),
+    }
+    let mut report = wallet.run_payouts(&payroll, "1234");
+    for attempt in 1..=3 {
+        let retry = match &report {
+            Ok(report) => {
+                println!("Payroll run: {}", report.summary());
+                report.retry_batch()
+            }
+            Err(err) => {
+                println!("Payroll not run: {}", err);
+                None
+            }
+        };
+        let Some(retry) = retry else {
+            break;
+        };
+        println!(
+            "Retrying {} failed rows, attempt {}",
+            retry.rows.len(),
+            attempt
+        );
+        report = wallet.run_payouts(&retry, "1234");
+    }
+    if let Ok(report) = &report {
+        print!("{}", report.to_csv());
+    }
+    // The same batch with a paid row edited is refused, not taken as paid.
+    let mut edited = payroll.clone();
+    edited.rows[0].amount = Money::naira(1_600);
+    match wallet.validate_payouts(&edited) {
+        Ok(plan) => println!("Edited payroll checks out: {} rows", plan.rows),
+        Err(err) => println!("Edited payroll rejected: {}", err),
+    }
+
+    println!("Audit head: {}", wallet.audit_head());
+    match wallet.verify_audit(Some(&published_head)) {
+        Ok(report) => println!("Audit log verified: {} entries", report.entries),
+        Err(err) => println!("Audit log failed verification: {}", err),
+    }
+    tamper_with_copies(&log_path)?;
+
+    let trial_balance = wallet.trial_balance();
+    for (account, line) in &trial_balance.lines {
+        println!(
+            "{:?}: debits {} credits {}",
+            account, line.debits, line.credits
+        );
+    }
+    println!("Books balanced: {}", trial_balance.is_balanced());
+
+    standing_order_schedule()?;
+
+    Ok(())
+}
+
+/// Tampers with copies of the wallet's files and shows the verifier
+/// catching it.
+fn tamper_with_copies(log_path: &Path) -> Result<(), WalletError> {
+    let dir = std::env::temp_dir().join(format!("wallet-audit-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    let log_copy = dir.join("wallet.log");
+    let audit_copy = AuditLog::path_for(&log_copy);
+    let reset = || -> io::Result<()> {
+        fs::copy(log_path, &log_copy)?;
+        fs::copy(AuditLog::path_for(log_path), &audit_copy)?;
+        Ok(())
+    };
+
+    // Someone points a deposit in the wallet log at another account.
+    reset()?;
+    let log = fs::read_to_string(&log_copy)?;
+    let edited = log.replacen(
+        r#""Deposited":{"account_number":"#,
+        r#""Deposited":{"account_number":1"#,
+        1,
+    );
+    fs::write(&log_copy, edited)?;
+    match audit::verify(&log_copy, None, None) {
+        Ok(report) => println!("Edited log passed audit: {:?}", report),
+        Err(err) => println!("Edited log caught: {}", err),
+    }
+
+    // Someone deletes an audit entry to hide a record.
+    reset()?;
+    let trail = fs::read_to_string(&audit_copy)?;
+    let kept: String = trail
+        .lines()
+        .enumerate()
+        .filter(|(index, _)| *index != 1)
+        .map(|(_, line)| format!("{line}\n"))
+        .collect();
+    fs::write(&audit_copy, kept)?;
+    match audit::verify(&log_copy, None, None) {
+        Ok(report) => println!("Trimmed audit log passed: {:?}", report),
+        Err(err) => println!("Trimmed audit log caught: {}", err),
+    }
+
+    // Someone appends a deposit to the wallet log without auditing it.
+    reset()?;
+    let log = fs::read_to_string(&log_copy)?;
+    if let Some(deposit) = log.lines().find(|line| line.contains(r#""Deposited""#)) {
+        fs::write(&log_copy, format!("{log}{deposit}\n"))?;
+        match audit::verify(&log_copy, None, None) {
+            Ok(report) => println!("Forged record passed audit: {:?}", report),
+            Err(err) => println!("Forged record caught: {}", err),
+        }
+        let reopened = Wallet::open(&log_copy)?;
+        if let Some(record) = reopened.unaudited() {
+            println!(
+                "Reopening set aside the unaudited {:?} in {}",
+                record,
+                AuditLog::unaudited_path_for(&log_copy).display()
+            );
+        }
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+fn print_balance(wallet: &Wallet, account_number: AccountNumber) {
+    if let Some(balance) = wallet.balance_of(account_number) {
+        println!(
+            "{} balance: {} (available {})",
+            account_number, balance.ledger, balance.available
+        );
+    }
+}
+
+/// Steps a weekly rent payment through five weeks on a manual clock: the
+/// tenant runs short once and tops up in time, then runs short again and
+/// the payment is missed once its retry window closes.
+fn standing_order_schedule() -> Result<(), WalletError> {
+    let clock = ManualClock::new(Date::new(2026, 1, 1).timestamp());
+    let mut wallet = Wallet::new();
+    wallet.set_clock(Arc::new(clock.clone()));
+    wallet.set_standing_order_retry_window(Duration::from_secs(2 * SECONDS_PER_DAY));
+
+    let tenant = wallet.open_account("Tenant", Bank::Kuda)?;
+    let landlord = wallet.open_account("Landlord", Bank::Kuda)?;
+    wallet.set_pin(tenant, "2468")?;
+    wallet.deposit_to(tenant, Money::naira(5_000), None)?;
+    let rent = wallet.add_standing_order(
+        tenant,
+        landlord,
+        Money::naira(2_000),
+        Frequency::Weekly(Weekday::Friday),
+        "2468",
+        None,
+    )?;
+
+    for day in 0..35 {
+        if day == 16 {
+            wallet.deposit_to(tenant, Money::naira(3_000), None)?;
+        }
+        for run in wallet.run_standing_orders()? {
+            let today = Date::from_timestamp(wallet.now());
+            match run.outcome {
+                RunOutcome::Paid(receipt) => println!(
+                    "{today}: order {} due {} paid, tenant has {}",
+                    run.order_id, run.due, receipt.balance_after
+                ),
+                RunOutcome::Retrying { until, reason } => println!(
+                    "{today}: order {} due {} retrying until {}: {}",
+                    run.order_id,
+                    run.due,
+                    Date::from_timestamp(until),
+                    reason
+                ),
+                RunOutcome::Missed(reason) => println!(
+                    "{today}: order {} due {} missed: {}",
+                    run.order_id, run.due, reason
+                ),
+            }
+        }
+        clock.advance(Duration::from_secs(SECONDS_PER_DAY));
+    }
+    for date in [Date::new(2026, 1, 9), Date::new(2026, 1, 16)] {
+        if let Some(balance) = wallet.balance_on(tenant, date) {
+            println!(
+                "Tenant's balance at the close of {}: {}",
+                date, balance.ledger
+            );
+        }
+    }
+    println!("{} records in history", wallet.history.len());
+    let rent = wallet.cancel_standing_order(rent.id)?;
+    println!(
+        "Standing order {} is {:?}, next due {}",
+        rent.id, rent.status, rent.next_due
+    );
+    println!("Tenant orders: {:?}", wallet.standing_orders_for(tenant));
+    print_balance(&wallet, landlord);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use super::Bank;
+use super::auth::to_hex;
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettlementStatus {
+    Successful,
+    Failed,
+    Reversed,
+    NotFound,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectorError {
+    #[error("{0:?} did not respond in time")]
+    Timeout(Bank),
+
+    #[error("{0:?} rejected the request: {1}")]
+    Rejected(Bank, String),
+
+    #[error("account {1} does not exist at {0:?}")]
+    UnknownAccount(Bank, AccountNumber),
+}
+
+/// The operations the wallet needs from another bank's side of a transfer.
+/// `reference` identifies one transfer end to end, so `requery` and
+/// `reverse` can ask about a `credit` or `debit` whose outcome was lost.
+pub trait BankConnector: fmt::Debug + Send {
+    fn bank(&self) -> Bank;
+
+    /// The account holder's name, used to confirm a beneficiary.
+    fn name_enquiry(&mut self, account_number: AccountNumber) -> Result<String, ConnectorError>;
+
+    fn credit(
+        &mut self,
+        reference: &str,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), ConnectorError>;
+
+    fn debit(
+        &mut self,
+        reference: &str,
+        account_number: AccountNumber,
+        amount: Money,
+    ) -> Result<(), ConnectorError>;
+
+    fn requery(&mut self, reference: &str) -> Result<SettlementStatus, ConnectorError>;
+
+    fn reverse(&mut self, reference: &str) -> Result<(), ConnectorError>;
+}
+
+/// A fresh switch reference starting with `prefix`. The random part means a
+/// retry after a failed attempt is never refused as a duplicate of it.
+pub fn new_reference(prefix: &str) -> Result<String, getrandom::Error> {
+    let mut nonce = [0u8; 8];
+    getrandom::fill(&mut nonce)?;
+    Ok(format!("{prefix}-{}", to_hex(&nonce)))
+}
+
+/// How the mock switch misbehaves. Rates are out of 1000 requests.
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchConfig {
+    pub latency: Duration,
+    pub timeout_per_mille: u32,
+    pub failure_per_mille: u32,
+    pub seed: u64,
+}
+
+/// An in-process stand-in for the interbank switch. Every `MockConnector`
+/// handed out by one switch shares its account directory and settlement
+/// records, the way real banks share NIP.
+///
+/// A simulated timeout is decided after the request may already have been
+/// applied, just like a lost response on a real network, so callers have to
+/// requery to learn what happened.
+#[derive(Debug, Clone)]
+pub struct MockSwitch {
+    state: Arc<Mutex<SwitchState>>,
+}
+
+#[derive(Debug)]
+pub struct MockConnector {
+    bank: Bank,
+    switch: MockSwitch,
+}
+
+#[derive(Debug)]
+struct SwitchState {
+    config: SwitchConfig,
+    rng: u64,
+    accounts: HashMap<(Bank, AccountNumber), String>,
+    settlements: HashMap<String, SettlementStatus>,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            timeout_per_mille: 0,
+            failure_per_mille: 0,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl MockSwitch {
+    pub fn new(config: SwitchConfig) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SwitchState {
+                config,
+                rng: config.seed.max(1),
+                accounts: HashMap::new(),
+                settlements: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn register_account(&self, bank: Bank, account_number: AccountNumber, name: &str) {
+        self.lock()
+            .accounts
+            .insert((bank, account_number), name.to_string());
+    }
+
+    pub fn connector(&self, bank: Bank) -> MockConnector {
+        MockConnector {
+            bank,
+            switch: self.clone(),
+        }
+    }
+
+    pub fn status(&self, reference: &str) -> SettlementStatus {
+        self.lock()
+            .settlements
+            .get(reference)
+            .copied()
+            .unwrap_or(SettlementStatus::NotFound)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SwitchState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Sleeps for the configured latency. The lock is not held while sleeping.
+    fn delay(&self) {
+        let latency = self.lock().config.latency;
+        if !latency.is_zero() {
+            thread::sleep(latency);
+        }
+    }
+
+    // Delays, then decides whether the request fails up front.
+    fn delay_and_maybe_fail(&self, bank: Bank) -> Result<(), ConnectorError> {
+        self.delay();
+        if self.lock().fails() {
+            return Err(ConnectorError::Rejected(bank, "switch error".to_string()));
+        }
+        Ok(())
+    }
+
+    fn maybe_time_out(&self, bank: Bank) -> Result<(), ConnectorError> {
+        let mut state = self.lock();
+        let timeout_per_mille = state.config.timeout_per_mille;
+        if state.roll(timeout_per_mille) {
+            return Err(ConnectorError::Timeout(bank));
+        }
+        Ok(())
+    }
+
+    fn settle(
+        &self,
+        bank: Bank,
+        reference: &str,
+        account_number: AccountNumber,
+    ) -> Result<(), ConnectorError> {
+        self.delay();
+        {
+            let mut state = self.lock();
+            if !state.accounts.contains_key(&(bank, account_number)) {
+                return Err(ConnectorError::UnknownAccount(bank, account_number));
+            }
+            if state.settlements.contains_key(reference) {
+                return Err(ConnectorError::Rejected(
+                    bank,
+                    format!("duplicate reference {reference}"),
+                ));
+            }
+            // A rejected request keeps its reference, so a requery finds it
+            // failed rather than missing.
+            let status = if state.fails() {
+                SettlementStatus::Failed
+            } else {
+                SettlementStatus::Successful
+            };
+            state.settlements.insert(reference.to_string(), status);
+            if status == SettlementStatus::Failed {
+                return Err(ConnectorError::Rejected(bank, "switch error".to_string()));
+            }
+        }
+        self.maybe_time_out(bank)
+    }
+}
+
+impl SwitchState {
+    fn fails(&mut self) -> bool {
+        let failure_per_mille = self.config.failure_per_mille;
+        self.roll(failure_per_mille)
+    }
+
+    // xorshift64: deterministic for a given seed, which keeps runs repeatable.
+    fn roll(&mut self, per_mille: u32) -> bool {
+        if per_mille == 0 {
+            return false;
+        }
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % 1000) < per_mille as u64
+    }
+}
+
+impl BankConnector for MockConnector {
+    fn bank(&self) -> Bank {
+        self.bank
+    }
+
+    fn name_enquiry(&mut self, account_number: AccountNumber) -> Result<String, ConnectorError> {
+        self.switch.delay_and_maybe_fail(self.bank)?;
+        self.switch
+            .lock()
+            .accounts
+            .get(&(self.bank, account_number))
+            .cloned()
+            .ok_or(ConnectorError::UnknownAccount(self.bank, account_number))
+    }
+
+    fn credit(
+        &mut self,
+        reference: &str,
+        account_number: AccountNumber,
+        _amount: Money,
+    ) -> Result<(), ConnectorError> {
+        self.switch.settle(self.bank, reference, account_number)
+    }
+
+    fn debit(
+        &mut self,
+        reference: &str,
+        account_number: AccountNumber,
+        _amount: Money,
+    ) -> Result<(), ConnectorError> {
+        self.switch.settle(self.bank, reference, account_number)
+    }
+
+    fn requery(&mut self, reference: &str) -> Result<SettlementStatus, ConnectorError> {
+        self.switch.delay_and_maybe_fail(self.bank)?;
+        Ok(self.switch.status(reference))
+    }
+
+    fn reverse(&mut self, reference: &str) -> Result<(), ConnectorError> {
+        self.switch.delay_and_maybe_fail(self.bank)?;
+        let mut state = self.switch.lock();
+        match state.settlements.get_mut(reference) {
+            Some(status @ SettlementStatus::Successful) => {
+                *status = SettlementStatus::Reversed;
+                Ok(())
+            }
+            Some(SettlementStatus::Reversed) => Ok(()),
+            _ => Err(ConnectorError::Rejected(
+                self.bank,
+                format!("nothing to reverse for {reference}"),
+            )),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::io;
+
+use thiserror::Error;
+
+use super::Bank;
+use super::adjustments::DisputeState;
+use super::audit::AuditError;
+use super::calendar::Date;
+use super::connector::ConnectorError;
+use super::fees::FeeError;
+use super::fx::{Amount, FxError};
+use super::kyc::{KycTier, LimitKind};
+use super::lifecycle::AccountState;
+use super::money::{Money, MoneyParseError};
+use super::nuban::{AccountNumber, NubanError};
+use super::payouts::PayoutError;
+
+#[derive(Error, Debug)]
+pub enum WalletError {
+    #[error("account {0} not found")]
+    AccountNotFound(AccountNumber),
+
+    #[error("account {0} already exists")]
+    DuplicateAccount(AccountNumber),
+
+    #[error(transparent)]
+    InvalidAccountNumber(#[from] NubanError),
+
+    #[error("account {account} is {state:?}")]
+    AccountNotActive {
+        account: AccountNumber,
+        state: AccountState,
+    },
+
+    #[error("account {account} cannot move from {from:?} to {to:?}")]
+    InvalidStateChange {
+        account: AccountNumber,
+        from: AccountState,
+        to: AccountState,
+    },
+
+    #[error("account {account} still holds {balance}")]
+    BalanceNotZero {
+        account: AccountNumber,
+        balance: Money,
+    },
+
+    #[error("account {0} has funds on hold")]
+    HoldsOutstanding(AccountNumber),
+
+    #[error("insufficient funds in account {account}: needed {needed}, available {available}")]
+    InsufficientFunds {
+        account: AccountNumber,
+        needed: Money,
+        available: Money,
+    },
+
+    #[error("{kind:?} limit of {limit} exceeded on account {account}: {attempted}")]
+    LimitExceeded {
+        account: AccountNumber,
+        kind: LimitKind,
+        limit: Money,
+        attempted: Money,
+    },
+
+    #[error("account {account} cannot move from {from:?} to {to:?}")]
+    InvalidTierChange {
+        account: AccountNumber,
+        from: KycTier,
+        to: KycTier,
+    },
+
+    #[error("account {0} has no transaction PIN")]
+    PinNotSet(AccountNumber),
+
+    #[error("account {0} already has a transaction PIN")]
+    PinAlreadySet(AccountNumber),
+
+    #[error("incorrect PIN for account {account}, {attempts_left} attempts left")]
+    IncorrectPin {
+        account: AccountNumber,
+        attempts_left: u32,
+    },
+
+    #[error("account {0} is locked after too many wrong PINs; reset the PIN to unlock it")]
+    PinLocked(AccountNumber),
+
+    #[error("a PIN must be 4 to 6 digits")]
+    InvalidPinFormat,
+
+    #[error("reset token for account {0} is invalid or has expired")]
+    InvalidResetToken(AccountNumber),
+
+    #[error("could not generate random bytes: {0}")]
+    Random(String),
+
+    #[error("amount must be more than zero")]
+    ZeroAmount,
+
+    #[error("amount would overflow the balance")]
+    Overflow,
+
+    #[error("cannot transfer from account {0} to itself")]
+    SameAccount(AccountNumber),
+
+    #[error("standing order {0} not found")]
+    StandingOrderNotFound(u64),
+
+    #[error("standing order {0} is not active")]
+    StandingOrderNotActive(u64),
+
+    #[error("end of day for {0} has already run")]
+    EndOfDayAlreadyRun(Date),
+
+    #[error("end of day for {0} cannot run before the day is over")]
+    EndOfDayInFuture(Date),
+
+    #[error("record dated {at} is older than the last record, dated {last}")]
+    OutOfOrder { at: u64, last: u64 },
+
+    #[error("statement range {from} to {to} ends before it starts")]
+    InvalidDateRange { from: Date, to: Date },
+
+    #[error("insufficient {} in account {account}: needed {needed}, available {available}", .needed.currency)]
+    InsufficientCurrency {
+        account: AccountNumber,
+        needed: Amount,
+        available: Amount,
+    },
+
+    #[error(transparent)]
+    Fx(#[from] FxError),
+
+    #[error(transparent)]
+    Fees(#[from] FeeError),
+
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+
+    #[error("the wallet is not backed by a log")]
+    NotPersistent,
+
+    #[error(transparent)]
+    Payout(#[from] PayoutError),
+
+    #[error("invalid amount: {0}")]
+    InvalidAmount(#[from] MoneyParseError),
+
+    #[error("account {account} is registered to {found}, not {expected}")]
+    NameMismatch {
+        account: AccountNumber,
+        expected: String,
+        found: String,
+    },
+
+    #[error("settlement failed: {0}")]
+    Settlement(#[from] ConnectorError),
+
+    #[error("settlement {0} could not be confirmed or reversed")]
+    SettlementUnknown(String),
+
+    #[error("no connector to {0:?} is configured")]
+    NoConnector(Bank),
+
+    #[error("hold {0} not found")]
+    HoldNotFound(u64),
+
+    #[error("hold {0} is no longer active")]
+    HoldNotActive(u64),
+
+    #[error("cannot capture {requested} on hold {hold}, only {held} is held")]
+    CaptureExceedsHold {
+        hold: u64,
+        requested: Money,
+        held: Money,
+    },
+
+    #[error("transaction {0} not found")]
+    TransactionNotFound(u64),
+
+    #[error("transaction {0} cannot be reversed")]
+    NotReversible(u64),
+
+    #[error("transaction {0} cannot be refunded")]
+    NotRefundable(u64),
+
+    #[error(
+        "cannot refund {requested} on transaction {transaction}, only {refundable} is refundable"
+    )]
+    RefundExceedsOriginal {
+        transaction: u64,
+        requested: Money,
+        refundable: Money,
+    },
+
+    #[error("transaction {0} cannot be disputed")]
+    NotDisputable(u64),
+
+    #[error("transaction {0} has an open dispute")]
+    TransactionDisputed(u64),
+
+    #[error("transaction {transaction} was settled by dispute {dispute}, which was {state:?}")]
+    DisputeDecided {
+        transaction: u64,
+        dispute: u64,
+        state: DisputeState,
+    },
+
+    #[error("dispute {0} not found")]
+    DisputeNotFound(u64),
+
+    #[error("dispute {dispute} cannot move from {from:?} to {to:?}")]
+    InvalidDisputeTransition {
+        dispute: u64,
+        from: DisputeState,
+        to: DisputeState,
+    },
+
+    #[error("idempotency key {0} was already used for a different request")]
+    IdempotencyConflict(String),
+
+    #[error("the wallet service has stopped")]
+    ServiceStopped,
+
+    #[error("could not serialize: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("storage error: {0}")]
+    Storage(#[from] io::Error),
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::Bank;
+use super::money::Money;
+
+/// How one bank prices an inter-bank transfer sent by its customers.
+///
+/// The fee is `flat` plus `percentage_bps` basis points of the amount
+/// (100 bps = 1%), limited to `cap` when one is set. The first
+/// `free_per_month` inter-bank transfers each calendar month cost nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeeSchedule {
+    flat: Money,
+    percentage_bps: u32,
+    cap: Option<Money>,
+    free_per_month: u32,
+}
+
+/// The fee a transfer would be charged, worked out before anything moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeePreview {
+    pub amount: Money,
+    pub fee: Money,
+    pub total_debit: Money,
+    pub inter_bank: bool,
+    pub free_transfers_left: u32,
+}
+
+/// Every bank's schedule. A fee file has one line per bank, such as
+/// `Kuda flat=10 percentage_bps=50 cap=50 free_per_month=2`, with amounts in
+/// naira; settings left out are zero. Blank lines and lines starting with
+/// `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedules {
+    by_bank: HashMap<Bank, FeeSchedule>,
+}
+
+#[derive(Error, Debug)]
+pub enum FeeError {
+    #[error("fee file line {line}: {message}")]
+    FeeFile { line: usize, message: String },
+
+    #[error("fee file: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl FeeSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn flat(mut self, flat: Money) -> Self {
+        self.flat = flat;
+        self
+    }
+
+    pub fn percentage_bps(mut self, percentage_bps: u32) -> Self {
+        self.percentage_bps = percentage_bps;
+        self
+    }
+
+    pub fn cap(mut self, cap: Money) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    pub fn free_per_month(mut self, free_per_month: u32) -> Self {
+        self.free_per_month = free_per_month;
+        self
+    }
+
+    pub fn free_transfers_left(&self, used_this_month: u32) -> u32 {
+        self.free_per_month.saturating_sub(used_this_month)
+    }
+
+    /// Fee for one transfer of `amount`, given how many inter-bank transfers
+    /// the sender already made this month. The percentage part rounds down
+    /// to the kobo. `None` means the fee does not fit in `Money`.
+    pub fn fee_for(&self, amount: Money, used_this_month: u32) -> Option<Money> {
+        if self.free_transfers_left(used_this_month) > 0 {
+            return Some(Money::ZERO);
+        }
+
+        let percentage = amount.kobo() as u128 * self.percentage_bps as u128 / 10_000;
+        let percentage = Money::from_kobo(u64::try_from(percentage).ok()?);
+        let fee = self.flat.checked_add(percentage)?;
+        Some(match self.cap {
+            Some(cap) => fee.min(cap),
+            None => fee,
+        })
+    }
+}
+
+impl FeeSchedules {
+    pub fn new() -> Self {
+        Self {
+            by_bank: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FeeError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn set(&mut self, bank: Bank, schedule: FeeSchedule) {
+        self.by_bank.insert(bank, schedule);
+    }
+
+    /// Banks without a configured schedule charge nothing.
+    pub fn for_bank(&self, bank: Bank) -> FeeSchedule {
+        self.by_bank.get(&bank).copied().unwrap_or_default()
+    }
+}
+
+impl FromStr for FeeSchedules {
+    type Err = FeeError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut schedules = FeeSchedules::new();
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |message: String| FeeError::FeeFile {
+                line: index + 1,
+                message,
+            };
+            let mut words = line.split_whitespace();
+            let bank: Bank = words.next().unwrap_or_default().parse().map_err(fail)?;
+            if schedules.by_bank.contains_key(&bank) {
+                return Err(fail(format!("{bank:?} is listed twice")));
+            }
+            let mut schedule = FeeSchedule::new();
+            for setting in words {
+                let (key, value) = setting
+                    .split_once('=')
+                    .ok_or_else(|| fail(format!("expected `name=value`, found `{setting}`")))?;
+                let money = || {
+                    value
+                        .parse::<Money>()
+                        .map_err(|err| fail(format!("{key}: {err}")))
+                };
+                let count = || {
+                    value
+                        .parse::<u32>()
+                        .map_err(|_| fail(format!("{key}: `{value}` is not a whole number")))
+                };
+                schedule = match key {
+                    "flat" => schedule.flat(money()?),
+                    "percentage_bps" => schedule.percentage_bps(count()?),
+                    "cap" => schedule.cap(money()?),
+                    "free_per_month" => schedule.free_per_month(count()?),
+                    _ => return Err(fail(format!("unknown setting `{key}`"))),
+                };
+            }
+            schedules.set(bank, schedule);
+        }
+        Ok(schedules)
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::money::Money;
+
+/// Currencies an account can hold. Every one of them has 100 minor units
+/// (kobo, cents, pence), so a `Money` value is read in the minor unit of
+/// whatever currency it is paired with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    Ngn,
+    Usd,
+    Gbp,
+}
+
+/// A `Money` value tagged with its currency, shown as `$1,250.00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount {
+    pub currency: Currency,
+    pub value: Money,
+}
+
+/// Price of one unit of a currency in naira, in millionths of a naira, so
+/// rates like 1550.254321 are exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Rate(u64);
+
+/// Supplies mid-market rates and the spread charged around them. The
+/// wallet asks for rates at conversion time, so a provider can be swapped
+/// or reloaded without touching anything already converted.
+pub trait RateProvider: fmt::Debug + Send {
+    fn mid_rate(&self, currency: Currency) -> Option<Rate>;
+
+    /// Basis points kept from every conversion as FX income, at most 10,000.
+    fn spread_bps(&self) -> u32;
+}
+
+/// Rates read from a text file with one `CODE rate` line per currency, the
+/// rate being naira per unit, and an optional `spread_bps N` line with N no
+/// more than 10000. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: HashMap<Currency, Rate>,
+    spread_bps: u32,
+}
+
+/// The result of converting `sold` into `bought`. `mid` is what `sold` is
+/// worth at the mid-market rate, rounded to the nearest minor unit;
+/// `spread` is the part of it the wallet keeps, so `bought + spread = mid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FxQuote {
+    pub sold: Amount,
+    pub bought: Amount,
+    pub mid: Amount,
+    pub spread: Amount,
+}
+
+#[derive(Error, Debug)]
+pub enum FxError {
+    #[error("no rate for {0}")]
+    RateUnavailable(Currency),
+
+    #[error("cannot convert {0} into itself")]
+    SameCurrency(Currency),
+
+    #[error("{0} is too small to convert")]
+    TooSmall(Amount),
+
+    #[error("{0} is too large to convert")]
+    TooLarge(Amount),
+
+    #[error("a spread of {0} basis points is more than the whole amount")]
+    InvalidSpread(u32),
+
+    #[error("rate file line {line}: {message}")]
+    RateFile { line: usize, message: String },
+
+    #[error("rate file: {0}")]
+    Io(#[from] io::Error),
+}
+
+const RATE_SCALE: u64 = 1_000_000;
+const BASIS_POINTS: u32 = 10_000;
+
+impl Currency {
+    pub const ALL: [Currency; 3] = [Currency::Ngn, Currency::Usd, Currency::Gbp];
+
+    pub const fn code(self) -> &'static str {
+        match self {
+            Currency::Ngn => "NGN",
+            Currency::Usd => "USD",
+            Currency::Gbp => "GBP",
+        }
+    }
+
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            Currency::Ngn => "₦",
+            Currency::Usd => "$",
+            Currency::Gbp => "£",
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Currency::ALL
+            .into_iter()
+            .find(|currency| currency.code().eq_ignore_ascii_case(input.trim()))
+            .ok_or_else(|| format!("unknown currency `{input}`"))
+    }
+}
+
+impl Amount {
+    pub const fn new(currency: Currency, value: Money) -> Self {
+        Self { currency, value }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.value.with_symbol(self.currency.symbol()))
+    }
+}
+
+impl Rate {
+    pub const ONE: Rate = Rate(RATE_SCALE);
+}
+
+/// Parses naira per unit, such as `1550.25`, to at most six decimal places.
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{input}` is not a valid rate");
+        let (whole, fraction) = input.trim().split_once('.').unwrap_or((input.trim(), ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) || fraction.len() > 6 {
+            return Err(invalid());
+        }
+        let whole: u64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: u64 = format!("{fraction:0<6}").parse().map_err(|_| invalid())?;
+        let rate = whole
+            .checked_mul(RATE_SCALE)
+            .and_then(|scaled| scaled.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        if rate == 0 {
+            return Err(invalid());
+        }
+        Ok(Rate(rate))
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:06}", self.0 / RATE_SCALE, self.0 % RATE_SCALE)
+    }
+}
+
+impl RateTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FxError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl FromStr for RateTable {
+    type Err = FxError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut table = RateTable::default();
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |message: String| FxError::RateFile {
+                line: index + 1,
+                message,
+            };
+            let (key, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| fail(format!("expected `CODE rate`, found `{line}`")))?;
+            if key == "spread_bps" {
+                table.spread_bps = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|spread| *spread <= BASIS_POINTS)
+                    .ok_or_else(|| fail(format!("`{}` is not a spread", value.trim())))?;
+                continue;
+            }
+            let currency: Currency = key.parse().map_err(fail)?;
+            if currency == Currency::Ngn {
+                return Err(fail("the naira rate is always 1".to_string()));
+            }
+            table.rates.insert(currency, value.parse().map_err(fail)?);
+        }
+        Ok(table)
+    }
+}
+
+impl RateProvider for RateTable {
+    fn mid_rate(&self, currency: Currency) -> Option<Rate> {
+        match currency {
+            Currency::Ngn => Some(Rate::ONE),
+            _ => self.rates.get(&currency).copied(),
+        }
+    }
+
+    fn spread_bps(&self) -> u32 {
+        self.spread_bps
+    }
+}
+
+/// Prices converting `amount` of `from` into `to` through naira. The mid
+/// value rounds half to even; what the customer gets rounds down, and the
+/// difference, including that rounding, is spread.
+pub fn quote(
+    rates: &dyn RateProvider,
+    from: Currency,
+    to: Currency,
+    amount: Money,
+) -> Result<FxQuote, FxError> {
+    if from == to {
+        return Err(FxError::SameCurrency(from));
+    }
+    let from_rate = rates.mid_rate(from).ok_or(FxError::RateUnavailable(from))?;
+    let to_rate = rates.mid_rate(to).ok_or(FxError::RateUnavailable(to))?;
+    let sold = Amount::new(from, amount);
+
+    // Both sides count in hundredths, so the minor units cancel out.
+    let numerator = amount.kobo() as u128 * from_rate.0 as u128;
+    let denominator = to_rate.0 as u128;
+    let mid = round_half_even(numerator, denominator);
+    let spread_bps = rates.spread_bps();
+    let kept = BASIS_POINTS
+        .checked_sub(spread_bps)
+        .ok_or(FxError::InvalidSpread(spread_bps))?;
+    let bought = numerator * kept as u128 / (denominator * BASIS_POINTS as u128);
+
+    let too_large = |_| FxError::TooLarge(sold);
+    let mid = Money::from_kobo(u64::try_from(mid).map_err(too_large)?);
+    let bought = Money::from_kobo(u64::try_from(bought).map_err(too_large)?);
+    if bought.is_zero() {
+        return Err(FxError::TooSmall(sold));
+    }
+    // Rounding down never lands above the mid value.
+    let spread = mid.checked_sub(bought).unwrap_or(Money::ZERO);
+    Ok(FxQuote {
+        sold,
+        bought: Amount::new(to, bought),
+        mid: Amount::new(to, mid),
+        spread: Amount::new(to, spread),
+    })
+}
+
+fn round_half_even(numerator: u128, denominator: u128) -> u128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    match (remainder * 2).cmp(&denominator) {
+        std::cmp::Ordering::Less => quotient,
+        std::cmp::Ordering::Greater => quotient + 1,
+        std::cmp::Ordering::Equal => quotient + quotient % 2,
+    }
+}
@@ -0,0 +1,34 @@
+use super::storage::Record;
+
+/// Every record the wallet has applied, in order. Rebuilding the wallet as
+/// it stood at some moment replays the records up to that moment.
+#[derive(Debug, Default)]
+pub struct History {
+    records: Vec<Record>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, record: Record) {
+        self.records.push(record);
+    }
+
+    /// When the newest record happened, if there is one.
+    pub fn last_at(&self) -> Option<u64> {
+        self.records.last().map(Record::at)
+    }
+
+    /// The records that happened by `at`. The wallet never commits a record
+    /// older than the one before it, so these are always a prefix.
+    pub fn replay_to(&self, at: u64) -> &[Record] {
+        let end = self.records.partition_point(|record| record.at() <= at);
+        &self.records[..end]
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldStatus {
+    Active,
+    Captured,
+    Voided,
+    Expired,
+}
+
+/// Funds reserved on an account for a later capture. An active hold lowers
+/// the available balance but does not touch the ledger balance until it is
+/// captured.
+#[derive(Debug, Clone)]
+pub struct Hold {
+    pub id: u64,
+    pub account_number: AccountNumber,
+    pub amount: Money,
+    pub expires_at: u64,
+    pub status: HoldStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Holds {
+    holds: BTreeMap<u64, Hold>,
+}
+
+impl Hold {
+    /// Active and not yet past its expiry. A hold can be past its expiry
+    /// before the wallet has recorded it as `Expired`.
+    pub fn is_live(&self, now: u64) -> bool {
+        self.status == HoldStatus::Active && now < self.expires_at
+    }
+}
+
+impl Holds {
+    pub fn new() -> Self {
+        Self {
+            holds: BTreeMap::new(),
+        }
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.holds.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    pub fn place(&mut self, hold: Hold) {
+        self.holds.insert(hold.id, hold);
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Hold> {
+        self.holds.get(&id)
+    }
+
+    pub fn set_status(&mut self, id: u64, status: HoldStatus) {
+        if let Some(hold) = self.holds.get_mut(&id) {
+            hold.status = status;
+        }
+    }
+
+    /// Total reserved on `account_number` by holds that are still live.
+    pub fn held_for(&self, account_number: AccountNumber, now: u64) -> Money {
+        self.holds
+            .values()
+            .filter(|hold| hold.account_number == account_number && hold.is_live(now))
+            .fold(Money::ZERO, |total, hold| {
+                total.checked_add(hold.amount).unwrap_or(Money::MAX)
+            })
+    }
+
+    /// Holds still marked active whose expiry has passed.
+    pub fn lapsed(&self, now: u64) -> Vec<u64> {
+        self.holds
+            .values()
+            .filter(|hold| hold.status == HoldStatus::Active && now >= hold.expires_at)
+            .map(|hold| hold.id)
+            .collect()
+    }
+}
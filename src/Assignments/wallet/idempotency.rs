@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::auth::to_hex;
+use super::error::WalletError;
+use super::fx::{Amount, FxQuote};
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+/// Idempotency keys the wallet has already honoured, mapped to what the
+/// original call produced and a fingerprint of what that call asked for.
+/// Keys are forgotten once they are older than the retention window.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKeys {
+    retention: Duration,
+    seen: HashMap<String, Used>,
+}
+
+#[derive(Debug, Clone)]
+struct Used {
+    at: u64,
+    fingerprint: String,
+    outcome: Outcome,
+}
+
+/// A key as it is stored on the record of the call that used it, so the
+/// call and its key are written in the same append.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyKey {
+    pub key: String,
+    pub fingerprint: String,
+}
+
+/// What a keyed call produced, enough to answer a retry of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The ledger transaction on the caller's side.
+    Transaction(u64),
+    Hold(u64),
+    StandingOrder(u64),
+    Conversion(FxQuote),
+    /// The account's balance in the currency just after the deposit.
+    Balance(Amount),
+}
+
+/// A digest of one request: what it does and to which accounts, for how
+/// much. A key replayed with the same fingerprint is a retry; with another
+/// it is a different request reusing the key.
+pub fn fingerprint(operation: &str, accounts: &[AccountNumber], amount: Money) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(operation.as_bytes());
+    for account_number in accounts {
+        hasher.update(format!("|{account_number}").as_bytes());
+    }
+    hasher.update(format!("|{}", amount.kobo()).as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+impl IdempotencyKeys {
+    pub const DEFAULT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+    pub fn new() -> Self {
+        Self {
+            retention: Self::DEFAULT_RETENTION,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// What the call made with `key` produced, if the key is still
+    /// retained at `now`. A key first used for a request with a different
+    /// fingerprint is a conflict.
+    pub fn lookup(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+    ) -> Result<Option<Outcome>, WalletError> {
+        let Some(used) = self
+            .seen
+            .get(key)
+            .filter(|used| !self.expired(used.at, now))
+        else {
+            return Ok(None);
+        };
+        if used.fingerprint != fingerprint {
+            return Err(WalletError::IdempotencyConflict(key.to_string()));
+        }
+        Ok(Some(used.outcome))
+    }
+
+    pub fn remember(&mut self, key: IdempotencyKey, outcome: Outcome, at: u64) {
+        self.seen.insert(
+            key.key,
+            Used {
+                at,
+                fingerprint: key.fingerprint,
+                outcome,
+            },
+        );
+    }
+
+    pub fn prune(&mut self, now: u64) {
+        let retention = self.retention;
+        self.seen
+            .retain(|_, used| now.saturating_sub(used.at) < retention.as_secs());
+    }
+
+    fn expired(&self, at: u64, now: u64) -> bool {
+        now.saturating_sub(at) >= self.retention.as_secs()
+    }
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
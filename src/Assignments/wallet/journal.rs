@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::fx::Currency;
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+/// Accounts in the wallet's general ledger. Customer accounts are liabilities
+/// (money we owe the customer); cash-in-transit is the asset side that money
+/// arrives through and leaves by; fees collects fee income; suspense holds
+/// disputed money until the dispute is decided; interest expense is what
+/// savings interest costs the wallet.
+///
+/// Naira sits in `Customer` and `CashInTransit`. Other currencies have their
+/// own customer and cash accounts, and conversions pass through an FX
+/// position per currency, with the spread kept as FX income. An entry only
+/// ever posts one currency, so each currency balances on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerAccount {
+    Customer(AccountNumber),
+    CashInTransit,
+    Fees,
+    Suspense,
+    InterestExpense,
+    CustomerForeign(AccountNumber, Currency),
+    ForeignCash(Currency),
+    FxPosition(Currency),
+    FxIncome(Currency),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+#[derive(Debug, Clone)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub side: Side,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub memo: String,
+    pub postings: Vec<Posting>,
+}
+
+/// Totals are in kobo and kept as `u128` so that summing many postings
+/// cannot overflow.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrialBalanceLine {
+    pub debits: u128,
+    pub credits: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrialBalance {
+    pub lines: BTreeMap<LedgerAccount, TrialBalanceLine>,
+    pub total_debits: u128,
+    pub total_credits: u128,
+    pub matches_running_balances: bool,
+}
+
+/// Append-only double-entry journal. Every entry's debits equal its credits,
+/// so the net of all postings across all accounts is always zero.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+    // Net debit (debits minus credits) per account, kept in step with `entries`.
+    net_debits: HashMap<LedgerAccount, i128>,
+}
+
+/// Shown as `Customer 0000000015`, `FxPosition USD` and so on.
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LedgerAccount::Customer(account_number) => format!("Customer {account_number}"),
+            LedgerAccount::CustomerForeign(account_number, currency) => {
+                format!("Customer {account_number} {currency}")
+            }
+            LedgerAccount::ForeignCash(currency) => format!("ForeignCash {currency}"),
+            LedgerAccount::FxPosition(currency) => format!("FxPosition {currency}"),
+            LedgerAccount::FxIncome(currency) => format!("FxIncome {currency}"),
+            other => format!("{other:?}"),
+        };
+        f.pad(&name)
+    }
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: Side::Debit,
+            amount,
+        }
+    }
+
+    pub fn credit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: Side::Credit,
+            amount,
+        }
+    }
+
+    fn signed(&self) -> i128 {
+        match self.side {
+            Side::Debit => self.amount.kobo() as i128,
+            Side::Credit => -(self.amount.kobo() as i128),
+        }
+    }
+}
+
+impl TrialBalance {
+    pub fn is_balanced(&self) -> bool {
+        self.total_debits == self.total_credits && self.matches_running_balances
+    }
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            net_debits: HashMap::new(),
+        }
+    }
+
+    /// Posts a balanced entry. Unbalanced entries are a bug in the caller,
+    /// not a runtime condition, so they panic instead of returning an error.
+    pub fn post(&mut self, memo: &str, postings: Vec<Posting>, timestamp: u64) -> u64 {
+        let net: i128 = postings.iter().map(Posting::signed).sum();
+        assert_eq!(net, 0, "unbalanced journal entry: {memo}");
+
+        for posting in &postings {
+            *self.net_debits.entry(posting.account).or_insert(0) += posting.signed();
+        }
+
+        let id = self.entries.len() as u64 + 1;
+        self.entries.push(JournalEntry {
+            id,
+            timestamp,
+            memo: memo.to_string(),
+            postings,
+        });
+        id
+    }
+
+    /// Moves `amount` by debiting one account and crediting another.
+    pub fn transfer(
+        &mut self,
+        memo: &str,
+        debit: LedgerAccount,
+        credit: LedgerAccount,
+        amount: Money,
+        timestamp: u64,
+    ) -> u64 {
+        self.post(
+            memo,
+            vec![
+                Posting::debit(debit, amount),
+                Posting::credit(credit, amount),
+            ],
+            timestamp,
+        )
+    }
+
+    /// Net debit balance of any ledger account.
+    pub fn net_debit(&self, account: LedgerAccount) -> i128 {
+        self.net_debits.get(&account).copied().unwrap_or(0)
+    }
+
+    /// What the wallet owes a customer: the credit balance of their liability
+    /// account. A customer account never goes into debit.
+    pub fn customer_balance(&self, account_number: AccountNumber) -> Money {
+        let owed = -self.net_debit(LedgerAccount::Customer(account_number));
+        Money::from_kobo(u64::try_from(owed).unwrap_or(0))
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Rebuilds every account's totals from the raw postings rather than the
+    /// running balances, so it also catches drift between the two.
+    pub fn trial_balance(&self) -> TrialBalance {
+        let mut lines: BTreeMap<LedgerAccount, TrialBalanceLine> = BTreeMap::new();
+        let mut total_debits = 0;
+        let mut total_credits = 0;
+
+        for posting in self.entries.iter().flat_map(|entry| &entry.postings) {
+            let line = lines.entry(posting.account).or_default();
+            match posting.side {
+                Side::Debit => {
+                    line.debits += posting.amount.kobo() as u128;
+                    total_debits += posting.amount.kobo() as u128;
+                }
+                Side::Credit => {
+                    line.credits += posting.amount.kobo() as u128;
+                    total_credits += posting.amount.kobo() as u128;
+                }
+            }
+        }
+
+        let matches_running_balances = lines.iter().all(|(account, line)| {
+            line.debits as i128 - line.credits as i128 == self.net_debit(*account)
+        });
+
+        TrialBalance {
+            lines,
+            total_debits,
+            total_credits,
+            matches_running_balances,
+        }
+    }
+}
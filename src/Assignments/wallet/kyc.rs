@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use super::money::Money;
+
+/// KYC tiers modelled on the CBN three-tier framework. Every account starts
+/// at `Tier1` and can only move up.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum KycTier {
+    #[default]
+    Tier1,
+    Tier2,
+    Tier3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    MaxBalance,
+    SingleTransaction,
+    Daily,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierLimits {
+    pub max_balance: Money,
+    pub single_transaction: Money,
+    pub daily: Money,
+}
+
+impl KycTier {
+    pub const fn limits(self) -> TierLimits {
+        match self {
+            KycTier::Tier1 => TierLimits {
+                max_balance: Money::naira(300_000),
+                single_transaction: Money::naira(50_000),
+                daily: Money::naira(50_000),
+            },
+            KycTier::Tier2 => TierLimits {
+                max_balance: Money::naira(500_000),
+                single_transaction: Money::naira(100_000),
+                daily: Money::naira(200_000),
+            },
+            KycTier::Tier3 => TierLimits {
+                max_balance: Money::MAX,
+                single_transaction: Money::naira(5_000_000),
+                daily: Money::naira(25_000_000),
+            },
+        }
+    }
+}
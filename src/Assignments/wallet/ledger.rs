@@ -0,0 +1,248 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TransactionKind {
+    Deposit,
+    Withdrawal,
+    TransferIn,
+    TransferOut,
+    Capture,
+    Reversal,
+    Refund,
+    Chargeback,
+    Interest,
+    InterestForfeited,
+    Conversion,
+}
+
+impl TransactionKind {
+    pub const ALL: [TransactionKind; 11] = [
+        TransactionKind::Deposit,
+        TransactionKind::Withdrawal,
+        TransactionKind::TransferIn,
+        TransactionKind::TransferOut,
+        TransactionKind::Capture,
+        TransactionKind::Reversal,
+        TransactionKind::Refund,
+        TransactionKind::Chargeback,
+        TransactionKind::Interest,
+        TransactionKind::InterestForfeited,
+        TransactionKind::Conversion,
+    ];
+}
+
+/// Parses a kind's name, such as `TransferIn`, ignoring case.
+impl FromStr for TransactionKind {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        TransactionKind::ALL
+            .into_iter()
+            .find(|kind| format!("{kind:?}").eq_ignore_ascii_case(input.trim()))
+            .ok_or_else(|| format!("unknown transaction kind `{input}`"))
+    }
+}
+
+/// One immutable line in an account's history. `balance_after` is the
+/// account balance once this transaction, and any `fee` charged with it,
+/// was applied.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: u64,
+    pub timestamp: u64,
+    pub account_number: AccountNumber,
+    pub kind: TransactionKind,
+    pub amount: Money,
+    pub fee: Money,
+    pub counterparty: Option<AccountNumber>,
+    /// The transaction this one answers: the debit side of a transfer, or
+    /// the original of a reversal, refund or chargeback.
+    pub related: Option<u64>,
+    /// The payer's note, such as "March salary".
+    pub narration: Option<String>,
+    /// The switch reference of a transfer settled with another bank, which
+    /// a reversal or refund has to go back through.
+    pub settlement: Option<String>,
+    pub balance_after: Money,
+}
+
+/// Filters for `Ledger::history`. Timestamps are unix seconds and both ends
+/// of the range are inclusive.
+#[derive(Debug, Clone)]
+pub struct HistoryQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+    kinds: Vec<TransactionKind>,
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub items: Vec<Transaction>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    entries: Vec<Transaction>,
+}
+
+impl Transaction {
+    /// The id is assigned by `Ledger::record`.
+    pub fn new(
+        account_number: AccountNumber,
+        kind: TransactionKind,
+        amount: Money,
+        balance_after: Money,
+        timestamp: u64,
+    ) -> Self {
+        Self {
+            id: 0,
+            timestamp,
+            account_number,
+            kind,
+            amount,
+            fee: Money::ZERO,
+            counterparty: None,
+            related: None,
+            narration: None,
+            settlement: None,
+            balance_after,
+        }
+    }
+
+    pub fn counterparty(mut self, counterparty: AccountNumber) -> Self {
+        self.counterparty = Some(counterparty);
+        self
+    }
+
+    pub fn related(mut self, related: u64) -> Self {
+        self.related = Some(related);
+        self
+    }
+
+    pub fn fee(mut self, fee: Money) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    pub fn narration(mut self, narration: Option<String>) -> Self {
+        self.narration = narration;
+        self
+    }
+
+    pub fn settlement(mut self, settlement: Option<String>) -> Self {
+        self.settlement = settlement;
+        self
+    }
+}
+
+impl HistoryQuery {
+    pub fn new() -> Self {
+        Self {
+            from: None,
+            to: None,
+            kinds: Vec::new(),
+            offset: 0,
+            limit: 20,
+        }
+    }
+
+    pub fn between(mut self, from: u64, to: u64) -> Self {
+        self.from = Some(from);
+        self.to = Some(to);
+        self
+    }
+
+    pub fn kind(mut self, kind: TransactionKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = limit;
+        self
+    }
+
+    fn matches(&self, transaction: &Transaction) -> bool {
+        let after_start = self.from.is_none_or(|from| transaction.timestamp >= from);
+        let before_end = self.to.is_none_or(|to| transaction.timestamp <= to);
+        let kind_ok = self.kinds.is_empty() || self.kinds.contains(&transaction.kind);
+        after_start && before_end && kind_ok
+    }
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, mut transaction: Transaction) -> u64 {
+        let id = self.entries.len() as u64 + 1;
+        transaction.id = id;
+        self.entries.push(transaction);
+        id
+    }
+
+    pub fn entries(&self) -> &[Transaction] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Transaction> {
+        self.entries.get(id.checked_sub(1)? as usize)
+    }
+
+    pub fn latest_for(&self, account_number: AccountNumber) -> Option<&Transaction> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|t| t.account_number == account_number)
+    }
+
+    /// Newest transactions first, so page 0 is always the most recent activity.
+    pub fn history(&self, account_number: AccountNumber, query: &HistoryQuery) -> Page {
+        let matching: Vec<&Transaction> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|t| t.account_number == account_number && query.matches(t))
+            .collect();
+
+        Page {
+            total: matching.len(),
+            items: matching
+                .into_iter()
+                .skip(query.offset)
+                .take(query.limit)
+                .cloned()
+                .collect(),
+            offset: query.offset,
+            limit: query.limit,
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+use super::nuban::AccountNumber;
+
+/// Where an account is in its life. Only `Active` accounts can be debited;
+/// `Frozen` and `Dormant` accounts can still receive money; `Closed` is final.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountState {
+    #[default]
+    Active,
+    Frozen,
+    Dormant,
+    Closed,
+}
+
+/// One audited state change.
+#[derive(Debug, Clone)]
+pub struct StateChange {
+    pub account_number: AccountNumber,
+    pub from: AccountState,
+    pub to: AccountState,
+    pub reason: String,
+    pub at: u64,
+}
+
+impl AccountState {
+    pub fn can_move_to(self, next: AccountState) -> bool {
+        use AccountState::*;
+        matches!(
+            (self, next),
+            (Active, Frozen)
+                | (Dormant, Frozen)
+                | (Frozen, Active)
+                | (Active, Dormant)
+                | (Dormant, Active)
+                | (Active, Closed)
+                | (Dormant, Closed)
+        )
+    }
+
+    pub fn can_debit(self) -> bool {
+        self == AccountState::Active
+    }
+
+    pub fn can_credit(self) -> bool {
+        self != AccountState::Closed
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// An amount of naira held as a whole number of kobo, so there is no
+/// floating point anywhere in the money path. Paired with a `Currency` it
+/// counts cents or pence instead. Arithmetic is checked: an
+/// operation that would overflow or go negative returns `None`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Money(u64);
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MoneyParseError {
+    #[error("`{0}` is not a valid amount")]
+    Invalid(String),
+
+    #[error("`{0}` has more than two decimal places")]
+    TooPrecise(String),
+
+    #[error("`{0}` is too large")]
+    TooLarge(String),
+}
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+    pub const MAX: Money = Money(u64::MAX);
+
+    pub const fn from_kobo(kobo: u64) -> Self {
+        Self(kobo)
+    }
+
+    /// Whole naira, for amounts written in code. Panics if the amount cannot
+    /// be represented in kobo; use `checked_from_naira` for outside input.
+    pub const fn naira(naira: u64) -> Self {
+        match Self::checked_from_naira(naira) {
+            Some(money) => money,
+            None => panic!("naira amount overflows"),
+        }
+    }
+
+    pub const fn checked_from_naira(naira: u64) -> Option<Self> {
+        match naira.checked_mul(100) {
+            Some(kobo) => Some(Self(kobo)),
+            None => None,
+        }
+    }
+
+    pub const fn kobo(self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Formats as `5000.00`, with no symbol or grouping, for exports that
+    /// other programs read.
+    pub fn to_plain_string(self) -> String {
+        format!("{}.{:02}", self.0 / 100, self.0 % 100)
+    }
+
+    /// Formats the amount as hundredths of the currency `symbol` stands for,
+    /// e.g. `$1,250.00`.
+    pub fn with_symbol(self, symbol: &str) -> String {
+        let whole = (self.0 / 100).to_string();
+        let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+        for (index, digit) in whole.chars().enumerate() {
+            if index > 0 && (whole.len() - index).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        format!("{}{}.{:02}", symbol, grouped, self.0 % 100)
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+}
+
+/// Formats as `₦5,000.00`, honouring width and alignment so amounts line
+/// up in columns.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.with_symbol("₦"))
+    }
+}
+
+/// Parses naira amounts such as `5000`, `5000.5`, `5000.50` or `₦5,000.50`.
+impl FromStr for Money {
+    type Err = MoneyParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || MoneyParseError::Invalid(input.to_string());
+        let cleaned: String = input
+            .trim()
+            .trim_start_matches('₦')
+            .chars()
+            .filter(|c| *c != ',')
+            .collect();
+
+        let (whole, fraction) = match cleaned.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (cleaned.as_str(), ""),
+        };
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+        if fraction.len() > 2 {
+            return Err(MoneyParseError::TooPrecise(input.to_string()));
+        }
+
+        let too_large = || MoneyParseError::TooLarge(input.to_string());
+        let naira: u64 = whole.parse().map_err(|_| too_large())?;
+        let kobo: u64 = match fraction.len() {
+            0 => 0,
+            1 => fraction.parse::<u64>().map_err(|_| invalid())? * 10,
+            _ => fraction.parse().map_err(|_| invalid())?,
+        };
+
+        Money::checked_from_naira(naira)
+            .and_then(|money| money.checked_add(Money(kobo)))
+            .ok_or_else(too_large)
+    }
+}
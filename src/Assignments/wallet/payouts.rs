@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::Receipt;
+use super::auth::to_hex;
+use super::error::WalletError;
+use super::money::Money;
+use super::nuban::AccountNumber;
+use super::statement::csv_field;
+
+pub const MAX_NARRATION_CHARS: usize = 100;
+const CSV_HEADER: &str = "account_number,amount,narration";
+const BATCH_PREFIX: &str = "# batch:";
+
+/// One payment in a payout file. `line` is where it sits in the file, and
+/// together with the funding account and batch id it keys the payment, so
+/// the same row is never paid twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutRow {
+    pub line: usize,
+    pub account_number: AccountNumber,
+    pub amount: Money,
+    pub narration: String,
+}
+
+/// A salary or payout run: every row is paid by transfer out of `funding`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutBatch {
+    pub id: String,
+    pub funding: AccountNumber,
+    pub rows: Vec<PayoutRow>,
+}
+
+/// The payout row a transfer paid. It is written with the transfer, so the
+/// row stays paid for as long as the log is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutLine {
+    pub batch: String,
+    pub line: usize,
+    pub fingerprint: String,
+}
+
+/// A row already paid: the transfer that paid it and what the row said.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaidRow {
+    pub transaction_id: u64,
+    pub fingerprint: String,
+}
+
+/// Every payout row ever paid, by funding account, batch id and line.
+/// Unlike idempotency keys these never expire, so a batch can be run again
+/// at any time.
+#[derive(Debug, Clone, Default)]
+pub struct PaidRows {
+    rows: HashMap<(AccountNumber, String, usize), PaidRow>,
+}
+
+/// A row that cannot be paid, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Error, Debug)]
+pub enum PayoutError {
+    #[error("payout batch {0} has no rows")]
+    Empty(String),
+
+    #[error("payout batch {batch} has {} invalid rows:{}", .rows.len(), list(.rows))]
+    InvalidRows { batch: String, rows: Vec<RowError> },
+
+    #[error("row changed since transaction {0} paid it")]
+    RowChanged(u64),
+
+    #[error("payout file does not name its batch")]
+    NoBatchId,
+
+    #[error("payout file is batch {file}, not {given}")]
+    BatchMismatch { file: String, given: String },
+}
+
+/// What validation worked out: the money the batch will take out of the
+/// funding account, fees included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayoutPlan {
+    pub rows: usize,
+    pub amount: Money,
+    pub fees: Money,
+    pub total_debit: Money,
+}
+
+#[derive(Debug)]
+pub enum PayoutOutcome {
+    Paid(Receipt),
+    Failed(WalletError),
+}
+
+#[derive(Debug)]
+pub struct PayoutResult {
+    pub row: PayoutRow,
+    pub outcome: PayoutOutcome,
+}
+
+/// Per-row results of one run, in file order.
+#[derive(Debug)]
+pub struct PayoutReport {
+    pub batch: String,
+    pub funding: AccountNumber,
+    pub results: Vec<PayoutResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayoutSummary {
+    pub rows: usize,
+    pub paid: usize,
+    pub failed: usize,
+    pub amount_paid: Money,
+    pub fees: Money,
+    pub amount_failed: Money,
+}
+
+impl PayoutBatch {
+    /// Reads `account_number,amount,narration` rows, amounts in naira.
+    /// The batch is named by a `# batch: ID` line in the file or else by
+    /// `id`; one of the two is required, since the id is what stops a row
+    /// being paid twice. Blank lines, other `#` comments and a header row are
+    /// skipped; fields holding commas go in double quotes. Files written by
+    /// `to_csv` also carry a leading `line` column, which keeps the original
+    /// batch's line numbers. Every malformed row is reported, not just the
+    /// first.
+    pub fn parse(id: Option<&str>, funding: AccountNumber, csv: &str) -> Result<Self, PayoutError> {
+        let mut named = None;
+        let mut numbered = false;
+        let mut seen_row = false;
+        let mut lines = HashSet::new();
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+        for (index, text) in csv.lines().enumerate() {
+            let line = index + 1;
+            let trimmed = text.trim();
+            if let Some(batch) = trimmed.strip_prefix(BATCH_PREFIX) {
+                named = Some(batch.trim().to_string()).filter(|batch| !batch.is_empty());
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if !seen_row {
+                seen_row = true;
+                let header = trimmed.to_ascii_lowercase();
+                if header.starts_with("line,") {
+                    numbered = true;
+                    continue;
+                }
+                if header.starts_with("account_number") {
+                    continue;
+                }
+            }
+            match parse_row(line, text, numbered) {
+                // Rows are keyed by line, so a number used twice would let
+                // one row hide behind the other.
+                Ok(row) if !lines.insert(row.line) => errors.push(RowError {
+                    line,
+                    reason: format!("line number {} is used twice", row.line),
+                }),
+                Ok(row) => rows.push(row),
+                Err(reason) => errors.push(RowError { line, reason }),
+            }
+        }
+        let id = match (named, id) {
+            (Some(named), Some(given)) if named != given => {
+                return Err(PayoutError::BatchMismatch {
+                    file: named,
+                    given: given.to_string(),
+                });
+            }
+            (Some(named), _) => named,
+            (None, Some(given)) if !given.trim().is_empty() => given.trim().to_string(),
+            (None, _) => return Err(PayoutError::NoBatchId),
+        };
+        if !errors.is_empty() {
+            return Err(PayoutError::InvalidRows {
+                batch: id,
+                rows: errors,
+            });
+        }
+        if rows.is_empty() {
+            return Err(PayoutError::Empty(id));
+        }
+        Ok(Self { id, funding, rows })
+    }
+
+    /// The rows as a payout file that `parse` reads back as this same
+    /// batch, so rows paid since are still recognised.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{BATCH_PREFIX} {}\nline,{CSV_HEADER}\n", self.id);
+        for row in &self.rows {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{}",
+                row.line,
+                row.account_number,
+                row.amount.to_plain_string(),
+                csv_field(&row.narration)
+            );
+        }
+        csv
+    }
+}
+
+impl PayoutRow {
+    /// A hash of what the row pays, kept with the payment so a row edited
+    /// after it was paid is caught rather than taken as paid.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            format!(
+                "{}\n{}\n{}",
+                self.account_number,
+                self.amount.kobo(),
+                self.narration
+            )
+            .as_bytes(),
+        );
+        to_hex(&hasher.finalize())
+    }
+
+    /// Where paying this row of `batch` is recorded.
+    pub fn paid_as(&self, batch: &str) -> PayoutLine {
+        PayoutLine {
+            batch: batch.to_string(),
+            line: self.line,
+            fingerprint: self.fingerprint(),
+        }
+    }
+}
+
+impl PaidRows {
+    pub fn new() -> Self {
+        Self {
+            rows: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, funding: AccountNumber, payout: PayoutLine, transaction_id: u64) {
+        self.rows.insert(
+            (funding, payout.batch, payout.line),
+            PaidRow {
+                transaction_id,
+                fingerprint: payout.fingerprint,
+            },
+        );
+    }
+
+    pub fn get(&self, funding: AccountNumber, batch: &str, line: usize) -> Option<&PaidRow> {
+        self.rows.get(&(funding, batch.to_string(), line))
+    }
+}
+
+impl PayoutReport {
+    pub fn summary(&self) -> PayoutSummary {
+        let mut summary = PayoutSummary {
+            rows: self.results.len(),
+            paid: 0,
+            failed: 0,
+            amount_paid: Money::ZERO,
+            fees: Money::ZERO,
+            amount_failed: Money::ZERO,
+        };
+        for result in &self.results {
+            let add = |total: Money, amount| total.checked_add(amount).unwrap_or(Money::MAX);
+            match &result.outcome {
+                PayoutOutcome::Paid(receipt) => {
+                    summary.paid += 1;
+                    summary.amount_paid = add(summary.amount_paid, receipt.amount);
+                    summary.fees = add(summary.fees, receipt.fee);
+                }
+                PayoutOutcome::Failed(_) => {
+                    summary.failed += 1;
+                    summary.amount_failed = add(summary.amount_failed, result.row.amount);
+                }
+            }
+        }
+        summary
+    }
+
+    /// The failed rows as a batch of their own. It keeps this batch's id and
+    /// line numbers, so running it pays only what is still unpaid.
+    pub fn retry_batch(&self) -> Option<PayoutBatch> {
+        let rows: Vec<PayoutRow> = self
+            .results
+            .iter()
+            .filter(|result| matches!(result.outcome, PayoutOutcome::Failed(_)))
+            .map(|result| result.row.clone())
+            .collect();
+        (!rows.is_empty()).then(|| PayoutBatch {
+            id: self.batch.clone(),
+            funding: self.funding,
+            rows,
+        })
+    }
+
+    /// One line per row with its status, transaction id or failure reason.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("line,{CSV_HEADER},status,transaction_id,fee,reason\n");
+        for result in &self.results {
+            let row = &result.row;
+            let (status, transaction_id, fee, reason) = match &result.outcome {
+                PayoutOutcome::Paid(receipt) => (
+                    "paid",
+                    receipt.transaction_id.to_string(),
+                    receipt.fee.to_plain_string(),
+                    String::new(),
+                ),
+                PayoutOutcome::Failed(err) => {
+                    ("failed", String::new(), String::new(), err.to_string())
+                }
+            };
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                row.line,
+                row.account_number,
+                row.amount.to_plain_string(),
+                csv_field(&row.narration),
+                status,
+                transaction_id,
+                fee,
+                csv_field(&reason)
+            );
+        }
+        csv
+    }
+}
+
+impl fmt::Display for PayoutSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rows: {} paid ({}, fees {}), {} failed ({})",
+            self.rows, self.paid, self.amount_paid, self.fees, self.failed, self.amount_failed
+        )
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+fn list(rows: &[RowError]) -> String {
+    rows.iter().map(|row| format!("\n  {row}")).collect()
+}
+
+fn parse_row(line: usize, text: &str, numbered: bool) -> Result<PayoutRow, String> {
+    let fields = split_csv(text)?;
+    let (line, fields) = match (numbered, fields.as_slice()) {
+        (true, [number, rest @ ..]) => (
+            number
+                .parse()
+                .map_err(|_| format!("`{number}` is not a line number"))?,
+            rest,
+        ),
+        (_, fields) => (line, fields),
+    };
+    let [account_number, amount, narration] = fields else {
+        return Err(format!(
+            "expected account_number,amount,narration, found {} fields",
+            fields.len()
+        ));
+    };
+    let account_number = account_number
+        .parse::<AccountNumber>()
+        .map_err(|err| err.to_string())?;
+    let amount = amount.parse::<Money>().map_err(|err| err.to_string())?;
+    if amount.is_zero() {
+        return Err("amount must be more than zero".to_string());
+    }
+    let narration = narration.trim().to_string();
+    if narration.chars().count() > MAX_NARRATION_CHARS {
+        return Err(format!(
+            "narration is longer than {MAX_NARRATION_CHARS} characters"
+        ));
+    }
+    Ok(PayoutRow {
+        line,
+        account_number,
+        amount,
+        narration,
+    })
+}
+
+// Splits one CSV line; a doubled quote inside a quoted field is a literal quote.
+fn split_csv(text: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unclosed quote".to_string());
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use super::calendar::{Date, SECONDS_PER_DAY};
+use super::money::Money;
+
+// Daily interest on `b` kobo at `r` bps a year is b * r / (10_000 * 365)
+// kobo. Accrual keeps the numerator, so fractions of a kobo carry over from
+// day to day instead of being rounded away.
+const ACCRUAL_DENOMINATOR: u128 = 10_000 * 365;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InterestMethod {
+    /// Interest is earned on the principal: the balance less whatever paid
+    /// interest is still in it.
+    #[default]
+    Simple,
+    /// Interest is earned on interest too: on the balance plus whatever has
+    /// accrued but not yet been paid.
+    Compound,
+}
+
+/// A savings product: an annual rate in basis points (100 bps = 1%), how
+/// interest is worked out, and an optional fixed term. Withdrawing from a
+/// fixed-term account before it matures forfeits the interest it earned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavingsProduct {
+    annual_rate_bps: u32,
+    method: InterestMethod,
+    term_days: Option<u32>,
+}
+
+/// The savings side of an account. Interest accrues each day by the
+/// end-of-day batch and is paid on the last day of the month.
+#[derive(Debug, Clone)]
+pub struct Savings {
+    pub product: SavingsProduct,
+    pub locked_until: Option<u64>,
+    /// The last day interest was accrued for, if any.
+    pub accrued_through: Option<Date>,
+    // Paid interest still in the account. A debit spends principal first,
+    // so this is never more than what the last debit left behind.
+    interest_held: Money,
+    // Interest paid since the term started; this is what an early withdrawal gives back.
+    term_interest: Money,
+    accrued: u128,
+}
+
+impl SavingsProduct {
+    pub fn new(annual_rate_bps: u32) -> Self {
+        Self {
+            annual_rate_bps,
+            ..Self::default()
+        }
+    }
+
+    pub fn compound(mut self) -> Self {
+        self.method = InterestMethod::Compound;
+        self
+    }
+
+    pub fn fixed_term(mut self, term_days: u32) -> Self {
+        self.term_days = Some(term_days);
+        self
+    }
+}
+
+impl Savings {
+    pub fn new(product: SavingsProduct, opened_at: u64) -> Self {
+        let locked_until = product
+            .term_days
+            .map(|days| opened_at + days as u64 * SECONDS_PER_DAY);
+        Self {
+            product,
+            locked_until,
+            accrued_through: None,
+            interest_held: Money::ZERO,
+            term_interest: Money::ZERO,
+            accrued: 0,
+        }
+    }
+
+    pub fn is_locked(&self, at: u64) -> bool {
+        self.locked_until.is_some_and(|until| at < until)
+    }
+
+    /// Interest accrued so far this month, rounded down to the kobo.
+    pub fn accrued_interest(&self) -> Money {
+        Money::from_kobo((self.accrued / ACCRUAL_DENOMINATOR) as u64)
+    }
+
+    /// What a debit at `at` would cost on top of its amount.
+    pub fn early_withdrawal_penalty(&self, at: u64) -> Money {
+        if self.is_locked(at) {
+            self.term_interest
+        } else {
+            Money::ZERO
+        }
+    }
+
+    /// Accrues one day's interest on the balance at the end of `day`.
+    pub fn accrue(&mut self, day: Date, closing_balance: Money) {
+        let base = match self.product.method {
+            InterestMethod::Simple => closing_balance
+                .checked_sub(self.interest_held)
+                .unwrap_or(Money::ZERO),
+            InterestMethod::Compound => closing_balance
+                .checked_add(self.accrued_interest())
+                .unwrap_or(Money::MAX),
+        };
+        self.accrued += base.kobo() as u128 * self.product.annual_rate_bps as u128;
+        self.accrued_through = Some(day);
+    }
+
+    /// Takes the whole kobo of accrued interest for paying out. The fraction
+    /// of a kobo left over keeps accruing into next month.
+    pub fn take_interest(&mut self, at: u64) -> Money {
+        let interest = self.accrued_interest();
+        self.accrued %= ACCRUAL_DENOMINATOR;
+        self.interest_held = self
+            .interest_held
+            .checked_add(interest)
+            .unwrap_or(Money::MAX);
+        if self.is_locked(at) {
+            self.term_interest = self
+                .term_interest
+                .checked_add(interest)
+                .unwrap_or(Money::MAX);
+        }
+        interest
+    }
+
+    /// Ends the term early. Returns the paid interest to claw back, at most
+    /// `limit`; interest accrued but not yet paid is dropped.
+    pub fn break_lock(&mut self, limit: Money) -> Money {
+        let forfeited = self.term_interest.min(limit);
+        self.locked_until = None;
+        self.term_interest = Money::ZERO;
+        self.accrued = 0;
+        self.interest_held = self
+            .interest_held
+            .checked_sub(forfeited)
+            .unwrap_or(Money::ZERO);
+        forfeited
+    }
+
+    /// Notes a debit that left `balance` in the account.
+    pub fn debited(&mut self, balance: Money) {
+        self.interest_held = self.interest_held.min(balance);
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use super::error::WalletError;
+use super::money::Money;
+use super::nuban::AccountNumber;
+use super::{Balance, Receipt, Wallet};
+
+type Job = Box<dyn FnOnce(&mut Wallet) + Send>;
+
+/// A cloneable, thread-safe handle to a wallet.
+///
+/// The wallet itself lives on one worker thread and every request is a job
+/// sent over a channel, so operations from any number of threads are applied
+/// one at a time, in arrival order. There are no locks to take in the wrong
+/// order and no read-modify-write races between two transfers.
+#[derive(Debug, Clone)]
+pub struct WalletHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl WalletHandle {
+    /// Moves `wallet` onto its own thread. Joining the returned handle after
+    /// every `WalletHandle` clone is dropped gives the wallet back.
+    pub fn spawn(mut wallet: Wallet) -> (Self, JoinHandle<Wallet>) {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let worker = thread::spawn(move || {
+            for job in queue {
+                job(&mut wallet);
+            }
+            wallet
+        });
+        (Self { jobs }, worker)
+    }
+
+    /// Runs `job` against the wallet and waits for its result.
+    pub fn call<R, F>(&self, job: F) -> Result<R, WalletError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Wallet) -> R + Send + 'static,
+    {
+        let (reply, result) = mpsc::channel();
+        self.jobs
+            .send(Box::new(move |wallet| {
+                let _ = reply.send(job(wallet));
+            }))
+            .map_err(|_| WalletError::ServiceStopped)?;
+        result.recv().map_err(|_| WalletError::ServiceStopped)
+    }
+
+    // Each of these takes an optional idempotency key, as the `Wallet`
+    // methods they call do.
+
+    pub fn deposit_to(
+        &self,
+        account_number: AccountNumber,
+        amount: Money,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        let key = key.map(str::to_string);
+        self.call(move |wallet| wallet.deposit_to(account_number, amount, key.as_deref()))?
+    }
+
+    pub fn withdraw_from(
+        &self,
+        account_number: AccountNumber,
+        amount: Money,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        let pin = pin.to_string();
+        let key = key.map(str::to_string);
+        self.call(move |wallet| wallet.withdraw_from(account_number, amount, &pin, key.as_deref()))?
+    }
+
+    pub fn transfer(
+        &self,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        pin: &str,
+        key: Option<&str>,
+    ) -> Result<Receipt, WalletError> {
+        let pin = pin.to_string();
+        let key = key.map(str::to_string);
+        self.call(move |wallet| wallet.transfer(from, to, amount, &pin, key.as_deref()))?
+    }
+
+    pub fn balance_of(
+        &self,
+        account_number: AccountNumber,
+    ) -> Result<Option<Balance>, WalletError> {
+        self.call(move |wallet| wallet.balance_of(account_number))
+    }
+}
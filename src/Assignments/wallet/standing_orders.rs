@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::Receipt;
+use super::calendar::{Date, Weekday};
+use super::error::WalletError;
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+/// How often a standing order pays. A monthly order on a day the month does
+/// not have (say the 31st) pays on the month's last day instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frequency {
+    Daily,
+    Weekly(Weekday),
+    Monthly(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Active,
+    Cancelled,
+}
+
+/// A standing instruction to move `amount` from `from` to `to` on every
+/// date `frequency` falls on. Payments are due from midnight UTC.
+#[derive(Debug, Clone)]
+pub struct StandingOrder {
+    pub id: u64,
+    pub from: AccountNumber,
+    pub to: AccountNumber,
+    pub amount: Money,
+    pub frequency: Frequency,
+    pub next_due: Date,
+    pub status: OrderStatus,
+}
+
+/// What one scheduler run did with one due payment.
+#[derive(Debug)]
+pub struct StandingOrderRun {
+    pub order_id: u64,
+    pub due: Date,
+    pub outcome: RunOutcome,
+}
+
+#[derive(Debug)]
+pub enum RunOutcome {
+    Paid(Receipt),
+    /// Not enough money yet; the payment is tried again on later runs until
+    /// `until`.
+    Retrying {
+        until: u64,
+        reason: WalletError,
+    },
+    /// The payment was given up and the order moved on to its next date.
+    Missed(WalletError),
+}
+
+#[derive(Debug, Clone)]
+pub struct StandingOrders {
+    orders: BTreeMap<u64, StandingOrder>,
+    retry_window: Duration,
+}
+
+impl Frequency {
+    /// The first date on or after `from` that this frequency falls on.
+    pub fn first_on_or_after(self, from: Date) -> Date {
+        let mut date = from;
+        while !self.falls_on(date) {
+            date = date.next_day();
+        }
+        date
+    }
+
+    fn falls_on(self, date: Date) -> bool {
+        match self {
+            Frequency::Daily => true,
+            Frequency::Weekly(weekday) => date.weekday() == weekday,
+            Frequency::Monthly(day) => date.day == day.clamp(1, date.days_in_month()),
+        }
+    }
+}
+
+impl StandingOrders {
+    pub const DEFAULT_RETRY_WINDOW: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+    pub fn new() -> Self {
+        Self {
+            orders: BTreeMap::new(),
+            retry_window: Self::DEFAULT_RETRY_WINDOW,
+        }
+    }
+
+    pub fn set_retry_window(&mut self, retry_window: Duration) {
+        self.retry_window = retry_window;
+    }
+
+    pub fn retry_window(&self) -> Duration {
+        self.retry_window
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.orders.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    pub fn add(&mut self, order: StandingOrder) {
+        self.orders.insert(order.id, order);
+    }
+
+    pub fn get(&self, id: u64) -> Option<&StandingOrder> {
+        self.orders.get(&id)
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        if let Some(order) = self.orders.get_mut(&id) {
+            order.status = OrderStatus::Cancelled;
+        }
+    }
+
+    /// Moves the order past its current due date, whether it was paid or missed.
+    pub fn advance(&mut self, id: u64) {
+        if let Some(order) = self.orders.get_mut(&id) {
+            order.next_due = order.frequency.first_on_or_after(order.next_due.next_day());
+        }
+    }
+
+    /// Active orders with a payment due at `at`, oldest order first.
+    pub fn due(&self, at: u64) -> Vec<u64> {
+        self.orders
+            .values()
+            .filter(|order| order.status == OrderStatus::Active)
+            .filter(|order| order.next_due.timestamp() <= at)
+            .map(|order| order.id)
+            .collect()
+    }
+
+    pub fn for_account(&self, account_number: AccountNumber) -> Vec<&StandingOrder> {
+        self.orders
+            .values()
+            .filter(|order| order.from == account_number)
+            .collect()
+    }
+}
+
+impl Default for StandingOrders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
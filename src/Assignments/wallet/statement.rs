@@ -0,0 +1,260 @@
+use std::fmt::{Display, Write};
+
+use serde::{Serialize, Serializer};
+
+use super::Bank;
+use super::calendar::Date;
+use super::ledger::{Transaction, TransactionKind};
+use super::money::Money;
+use super::nuban::AccountNumber;
+
+/// An account statement for a range of whole days. Every line's `balance`
+/// is the running balance after it, so the opening balance plus credits
+/// minus debits always lands on the closing balance. In JSON, amounts are
+/// in kobo.
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    #[serde(serialize_with = "as_text")]
+    pub account_number: AccountNumber,
+    pub name: String,
+    pub bank: Bank,
+    #[serde(serialize_with = "as_text")]
+    pub from: Date,
+    #[serde(serialize_with = "as_text")]
+    pub to: Date,
+    pub opening_balance: Money,
+    pub lines: Vec<StatementLine>,
+    pub total_debits: Money,
+    pub total_credits: Money,
+    pub closing_balance: Money,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementLine {
+    pub transaction_id: u64,
+    pub timestamp: u64,
+    #[serde(serialize_with = "as_text")]
+    pub date: Date,
+    pub kind: TransactionKind,
+    pub description: String,
+    pub debit: Money,
+    pub credit: Money,
+    pub balance: Money,
+}
+
+const CSV_HEADER: &str = "date,transaction_id,kind,description,debit,credit,balance";
+const TEXT_RULE_WIDTH: usize = 104;
+
+impl Statement {
+    /// Builds the statement from the account's transactions inside the range,
+    /// oldest first. Whether a line is a debit or a credit comes from how it
+    /// moved the balance, which also covers reversals on either side of a
+    /// transfer. Returns `None` if a total overflows.
+    pub fn new(
+        account_number: AccountNumber,
+        name: &str,
+        bank: Bank,
+        (from, to): (Date, Date),
+        opening_balance: Money,
+        transactions: &[&Transaction],
+    ) -> Option<Self> {
+        let mut running = opening_balance;
+        let mut total_debits = Money::ZERO;
+        let mut total_credits = Money::ZERO;
+        let mut lines = Vec::with_capacity(transactions.len());
+
+        for transaction in transactions {
+            let balance = transaction.balance_after;
+            let (debit, credit) = match balance.checked_sub(running) {
+                Some(credit) => (Money::ZERO, credit),
+                None => (running.checked_sub(balance)?, Money::ZERO),
+            };
+            total_debits = total_debits.checked_add(debit)?;
+            total_credits = total_credits.checked_add(credit)?;
+            running = balance;
+            lines.push(StatementLine {
+                transaction_id: transaction.id,
+                timestamp: transaction.timestamp,
+                date: Date::from_timestamp(transaction.timestamp),
+                kind: transaction.kind,
+                description: describe(transaction),
+                debit,
+                credit,
+                balance,
+            });
+        }
+
+        Some(Self {
+            account_number,
+            name: name.to_string(),
+            bank,
+            from,
+            to,
+            opening_balance,
+            lines,
+            total_debits,
+            total_credits,
+            closing_balance: running,
+        })
+    }
+
+    /// One row per transaction, bracketed by opening and closing balance rows.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        csv.push_str(CSV_HEADER);
+        csv.push('\n');
+        let _ = writeln!(
+            csv,
+            "{},,,Opening balance,,,{}",
+            self.from,
+            self.opening_balance.to_plain_string()
+        );
+        for line in &self.lines {
+            let _ = writeln!(
+                csv,
+                "{},{},{:?},{},{},{},{}",
+                line.date,
+                line.transaction_id,
+                line.kind,
+                csv_field(&line.description),
+                plain_or_blank(line.debit),
+                plain_or_blank(line.credit),
+                line.balance.to_plain_string()
+            );
+        }
+        let _ = writeln!(
+            csv,
+            "{},,,Closing balance,{},{},{}",
+            self.to,
+            self.total_debits.to_plain_string(),
+            self.total_credits.to_plain_string(),
+            self.closing_balance.to_plain_string()
+        );
+        csv
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// A fixed-width layout for printing or attaching to an email.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let rule = "-".repeat(TEXT_RULE_WIDTH);
+        let _ = writeln!(text, "STATEMENT OF ACCOUNT");
+        let _ = writeln!(
+            text,
+            "Account : {} ({}, {:?})",
+            self.account_number, self.name, self.bank
+        );
+        let _ = writeln!(text, "Period  : {} to {}", self.from, self.to);
+        let _ = writeln!(text, "{rule}");
+        let _ = writeln!(
+            text,
+            "{:<10}  {:>6}  {:<36}  {:>14}  {:>14}  {:>14}",
+            "Date", "Ref", "Description", "Debit", "Credit", "Balance"
+        );
+        let _ = writeln!(text, "{rule}");
+        let _ = writeln!(
+            text,
+            "{:<10}  {:>6}  {:<36}  {:>14}  {:>14}  {:>14}",
+            self.from, "", "Opening balance", "", "", self.opening_balance
+        );
+        for line in &self.lines {
+            let _ = writeln!(
+                text,
+                "{:<10}  {:>6}  {:<36}  {:>14}  {:>14}  {:>14}",
+                line.date,
+                line.transaction_id,
+                truncate(&line.description, 36),
+                shown_or_blank(line.debit),
+                shown_or_blank(line.credit),
+                line.balance
+            );
+        }
+        let _ = writeln!(text, "{rule}");
+        let _ = writeln!(
+            text,
+            "{:<10}  {:>6}  {:<36}  {:>14}  {:>14}  {:>14}",
+            self.to,
+            "",
+            "Closing balance",
+            self.total_debits,
+            self.total_credits,
+            self.closing_balance
+        );
+        text
+    }
+}
+
+fn describe(transaction: &Transaction) -> String {
+    let counterparty = transaction
+        .counterparty
+        .map(|account_number| account_number.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let related = transaction
+        .related
+        .map(|id| format!(" #{id}"))
+        .unwrap_or_default();
+    let description = match transaction.kind {
+        TransactionKind::Deposit => "Deposit".to_string(),
+        TransactionKind::Withdrawal => "Withdrawal".to_string(),
+        TransactionKind::TransferIn => format!("Transfer from {counterparty}"),
+        TransactionKind::TransferOut => format!("Transfer to {counterparty}"),
+        TransactionKind::Capture => "Card payment".to_string(),
+        TransactionKind::Reversal => format!("Reversal of{related}"),
+        TransactionKind::Refund => format!("Refund of{related}"),
+        TransactionKind::Chargeback => format!("Chargeback on{related}"),
+        TransactionKind::Interest => "Interest".to_string(),
+        TransactionKind::InterestForfeited => "Interest forfeited, early withdrawal".to_string(),
+        TransactionKind::Conversion => "Currency conversion".to_string(),
+    };
+    let description = match &transaction.narration {
+        Some(narration) => format!("{description}: {narration}"),
+        None => description,
+    };
+    if transaction.fee.is_zero() {
+        description
+    } else {
+        format!("{description} (fee {})", transaction.fee)
+    }
+}
+
+fn plain_or_blank(amount: Money) -> String {
+    if amount.is_zero() {
+        String::new()
+    } else {
+        amount.to_plain_string()
+    }
+}
+
+fn shown_or_blank(amount: Money) -> String {
+    if amount.is_zero() {
+        String::new()
+    } else {
+        amount.to_string()
+    }
+}
+
+/// Quotes `field` for a CSV cell when it holds a comma, quote or newline.
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut cut: String = text.chars().take(width - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+fn as_text<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
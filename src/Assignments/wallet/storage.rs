@@ -0,0 +1,324 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::Bank;
+use super::adjustments::DisputeState;
+use super::auth::PinCredential;
+use super::fx::Currency;
+use super::idempotency::IdempotencyKey;
+use super::kyc::KycTier;
+use super::lifecycle::AccountState;
+use super::money::Money;
+use super::nuban::AccountNumber;
+use super::payouts::PayoutLine;
+use super::savings::SavingsProduct;
+use super::standing_orders::Frequency;
+
+/// One durable change to the wallet. The log on disk is a sequence of these,
+/// one JSON object per line, and replaying them in order rebuilds the wallet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Record {
+    AccountAdded {
+        name: String,
+        bank: Bank,
+        account_number: AccountNumber,
+        savings: Option<SavingsProduct>,
+        at: u64,
+    },
+    AccountStateChanged {
+        account_number: AccountNumber,
+        state: AccountState,
+        reason: String,
+        at: u64,
+    },
+    PinSet {
+        account_number: AccountNumber,
+        credential: PinCredential,
+        at: u64,
+    },
+    PinFailed {
+        account_number: AccountNumber,
+        locked: bool,
+        at: u64,
+    },
+    PinVerified {
+        account_number: AccountNumber,
+        at: u64,
+    },
+    PinResetRequested {
+        account_number: AccountNumber,
+        token_hash: String,
+        expires_at: u64,
+        at: u64,
+    },
+    TierUpgraded {
+        account_number: AccountNumber,
+        tier: KycTier,
+        at: u64,
+    },
+    Deposited {
+        account_number: AccountNumber,
+        amount: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    Withdrawn {
+        account_number: AccountNumber,
+        amount: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    Transferred {
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        fee: Money,
+        standing_order: Option<u64>,
+        narration: Option<String>,
+        /// The switch reference of a transfer settled with another bank.
+        settlement: Option<String>,
+        /// The payout row this transfer paid.
+        payout: Option<PayoutLine>,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    HoldPlaced {
+        hold_id: u64,
+        account_number: AccountNumber,
+        amount: Money,
+        expires_at: u64,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    HoldCaptured {
+        hold_id: u64,
+        amount: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    HoldVoided {
+        hold_id: u64,
+        at: u64,
+    },
+    HoldExpired {
+        hold_id: u64,
+        at: u64,
+    },
+    Reversed {
+        transaction_id: u64,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    Refunded {
+        transaction_id: u64,
+        amount: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    DisputeOpened {
+        dispute_id: u64,
+        transaction_id: u64,
+        amount: Money,
+        at: u64,
+    },
+    DisputeMoved {
+        dispute_id: u64,
+        state: DisputeState,
+        at: u64,
+    },
+    StandingOrderCreated {
+        order_id: u64,
+        from: AccountNumber,
+        to: AccountNumber,
+        amount: Money,
+        frequency: Frequency,
+        first_due: u64,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    StandingOrderCancelled {
+        order_id: u64,
+        at: u64,
+    },
+    /// A payment was given up; `reason` is the last error it failed with.
+    StandingOrderMissed {
+        order_id: u64,
+        reason: String,
+        at: u64,
+    },
+    CurrencyDeposited {
+        account_number: AccountNumber,
+        currency: Currency,
+        amount: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    /// `sold` of `from` became `bought` of `to`; `mid` is what `sold` was
+    /// worth at the mid-market rate, so the rate used is kept with it.
+    Converted {
+        account_number: AccountNumber,
+        from: Currency,
+        to: Currency,
+        sold: Money,
+        bought: Money,
+        mid: Money,
+        key: Option<IdempotencyKey>,
+        at: u64,
+    },
+    /// The end-of-day batch ran for the day starting at `day`.
+    EndOfDay {
+        day: u64,
+        at: u64,
+    },
+}
+
+/// Append-only record log. Each append is flushed to disk with `sync_data`
+/// before it returns, so a record that was acknowledged survives a crash.
+#[derive(Debug)]
+pub struct Storage {
+    path: PathBuf,
+    file: File,
+}
+
+impl Record {
+    /// When the change was made, in unix seconds.
+    pub fn at(&self) -> u64 {
+        match self {
+            Record::AccountAdded { at, .. }
+            | Record::AccountStateChanged { at, .. }
+            | Record::PinSet { at, .. }
+            | Record::PinFailed { at, .. }
+            | Record::PinVerified { at, .. }
+            | Record::PinResetRequested { at, .. }
+            | Record::TierUpgraded { at, .. }
+            | Record::Deposited { at, .. }
+            | Record::Withdrawn { at, .. }
+            | Record::Transferred { at, .. }
+            | Record::HoldPlaced { at, .. }
+            | Record::HoldCaptured { at, .. }
+            | Record::HoldVoided { at, .. }
+            | Record::HoldExpired { at, .. }
+            | Record::Reversed { at, .. }
+            | Record::Refunded { at, .. }
+            | Record::DisputeOpened { at, .. }
+            | Record::DisputeMoved { at, .. }
+            | Record::StandingOrderCreated { at, .. }
+            | Record::StandingOrderCancelled { at, .. }
+            | Record::StandingOrderMissed { at, .. }
+            | Record::CurrencyDeposited { at, .. }
+            | Record::Converted { at, .. }
+            | Record::EndOfDay { at, .. } => *at,
+        }
+    }
+}
+
+impl Storage {
+    /// Opens (or creates) the log at `path` and returns every record in it.
+    ///
+    /// A crash in the middle of an append can leave a final line without its
+    /// newline. That record was never acknowledged, so it is cut off rather
+    /// than treated as corruption. A damaged line anywhere else is an error.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, Vec<Record>)> {
+        let path = path.as_ref().to_path_buf();
+        let existed = path.exists();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        if !existed {
+            sync_parent_dir(&path)?;
+        }
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let complete = contents.rfind('\n').map_or(0, |index| index + 1);
+        if complete < contents.len() {
+            file.set_len(complete as u64)?;
+            file.sync_data()?;
+        }
+
+        let records = parse_records(&path, &contents[..complete])?;
+        Ok((Self { path, file }, records))
+    }
+
+    /// Opens the log at `path` without write access, so every append fails.
+    #[cfg(test)]
+    pub fn read_only(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Reads the records in the log at `path` without opening it for
+    /// writing or repairing a torn tail.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        let complete = contents.rfind('\n').map_or(0, |index| index + 1);
+        parse_records(path, &contents[..complete])
+    }
+
+    /// Writes `record` to the end of the log. If the write or the flush
+    /// fails, the log is cut back to where it was so a half-written line is
+    /// not left in front of the next append.
+    pub fn append(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+        line.push('\n');
+        let len = self.file.metadata()?.len();
+        let written = self
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data());
+        if written.is_err() {
+            // Best effort: the write error is the one worth reporting.
+            let _ = self.file.set_len(len);
+        }
+        written
+    }
+
+    /// Cuts the last record off the log.
+    pub fn drop_last(&mut self) -> io::Result<()> {
+        let mut contents = String::new();
+        File::open(&self.path)?.read_to_string(&mut contents)?;
+        let complete = contents.rfind('\n').map_or(0, |index| index + 1);
+        let last = contents[..complete.saturating_sub(1)]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        self.file.set_len(last as u64)?;
+        self.file.sync_data()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn parse_records(path: &Path, contents: &str) -> io::Result<Vec<Record>> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: line {}: {}", path.display(), index + 1, err),
+                )
+            })
+        })
+        .collect()
+}
+
+// A newly created file is only durable once its directory entry is.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
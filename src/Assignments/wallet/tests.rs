@@ -0,0 +1,2250 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::audit::AuditError;
+use super::calendar::Weekday;
+use super::connector::{MockSwitch, SwitchConfig};
+use super::fx::FxError;
+use super::money::MoneyParseError;
+use super::nuban::NubanError;
+use super::service::WalletHandle;
+use super::*;
+
+const PIN: &str = "1234";
+
+/// Answers every CLI PIN prompt with `PIN`.
+fn pin(_prompt: &str) -> io::Result<String> {
+    Ok(PIN.to_string())
+}
+
+/// A wallet on a stopped clock, at 09:00 UTC on `date`, with a cheap PIN
+/// hash so tests stay fast.
+fn wallet_on(date: Date) -> (Wallet, ManualClock) {
+    let clock = ManualClock::new(date.timestamp() + 9 * 60 * 60);
+    let mut wallet = Wallet::new();
+    wallet.set_clock(Arc::new(clock.clone()));
+    wallet.pin_policy = PinPolicy {
+        rounds: 1,
+        ..PinPolicy::default()
+    };
+    (wallet, clock)
+}
+
+/// Opens an account with `PIN` set and `naira` deposited.
+fn funded(wallet: &mut Wallet, name: &str, bank: Bank, naira: u64) -> AccountNumber {
+    let account_number = wallet.open_account(name, bank).unwrap();
+    wallet.set_pin(account_number, PIN).unwrap();
+    if naira > 0 {
+        wallet
+            .deposit_to(account_number, Money::naira(naira), None)
+            .unwrap();
+    }
+    account_number
+}
+
+fn ledger_balance(wallet: &Wallet, account_number: AccountNumber) -> Money {
+    wallet.balance_of(account_number).unwrap().ledger
+}
+
+/// A fresh directory for one test's files.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wallet-test-{}-{test}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn failed_transfer_moves_nothing_on_either_side() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 0);
+    let entries = wallet.journal_entries().len();
+    let transactions = wallet.ledger.entries().len();
+
+    assert!(matches!(
+        wallet.transfer(ada, bayo, Money::naira(1_001), PIN, None),
+        Err(WalletError::InsufficientFunds { .. })
+    ));
+    let nobody = AccountNumber::new(Bank::Kuda, 999).unwrap();
+    assert!(matches!(
+        wallet.transfer(ada, nobody, Money::naira(100), PIN, None),
+        Err(WalletError::AccountNotFound(_))
+    ));
+    wallet.freeze(ada, "fraud check").unwrap();
+    assert!(matches!(
+        wallet.transfer(ada, bayo, Money::naira(100), PIN, None),
+        Err(WalletError::AccountNotActive { .. })
+    ));
+
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+    assert_eq!(ledger_balance(&wallet, bayo), Money::ZERO);
+    assert_eq!(wallet.journal_entries().len(), entries);
+    assert_eq!(wallet.ledger.entries().len(), transactions);
+    assert!(wallet.trial_balance().matches_running_balances);
+}
+
+#[test]
+fn history_pages_newest_first_and_filters_by_kind_and_time() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let start = wallet.now();
+    for naira in [100, 200, 300] {
+        wallet.deposit_to(ada, Money::naira(naira), None).unwrap();
+        clock.advance(Duration::from_secs(60));
+    }
+    wallet
+        .withdraw_from(ada, Money::naira(50), PIN, None)
+        .unwrap();
+
+    let amounts = |query: HistoryQuery| {
+        let page = wallet.history(ada, &query).unwrap();
+        let amounts: Vec<u64> = page
+            .items
+            .iter()
+            .map(|transaction| transaction.amount.kobo() / 100)
+            .collect();
+        (page.total, amounts)
+    };
+    assert_eq!(amounts(HistoryQuery::new()), (4, vec![50, 300, 200, 100]));
+    assert_eq!(amounts(HistoryQuery::new().page(1, 2)), (4, vec![300, 200]));
+    assert_eq!(
+        amounts(HistoryQuery::new().kind(TransactionKind::Deposit)),
+        (3, vec![300, 200, 100])
+    );
+    assert_eq!(
+        amounts(HistoryQuery::new().between(start + 60, start + 120)),
+        (2, vec![300, 200])
+    );
+    assert_eq!(amounts(HistoryQuery::new().page(4, 20)), (4, vec![]));
+    let nobody = AccountNumber::new(Bank::Kuda, 999).unwrap();
+    assert!(wallet.history(nobody, &HistoryQuery::new()).is_none());
+}
+
+#[test]
+fn trial_balance_nets_to_zero_and_matches_customer_balances() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 500);
+    wallet
+        .transfer(ada, bayo, Money::naira(300), PIN, None)
+        .unwrap();
+    wallet
+        .withdraw_from(bayo, Money::naira(200), PIN, None)
+        .unwrap();
+
+    let trial = wallet.trial_balance();
+    assert!(trial.is_balanced());
+    assert_eq!(trial.total_debits, trial.total_credits);
+    for account_number in [ada, bayo] {
+        let line = trial.lines[&LedgerAccount::Customer(account_number)];
+        assert_eq!(
+            line.credits - line.debits,
+            ledger_balance(&wallet, account_number).kobo() as u128
+        );
+    }
+}
+
+#[test]
+fn reopened_wallet_picks_up_where_it_left_off() {
+    let dir = scratch_dir("reopen");
+    let path = dir.join("wallet.log");
+    let mut wallet = Wallet::open(&path).unwrap();
+    wallet.pin_policy.rounds = 1;
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 0);
+    wallet
+        .transfer(ada, bayo, Money::naira(400), PIN, None)
+        .unwrap();
+    let transactions = wallet.ledger.entries().len();
+    drop(wallet);
+
+    let mut wallet = Wallet::open(&path).unwrap();
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(600));
+    assert_eq!(ledger_balance(&wallet, bayo), Money::naira(400));
+    assert_eq!(wallet.ledger.entries().len(), transactions);
+    assert!(wallet.trial_balance().is_balanced());
+    wallet
+        .transfer(bayo, ada, Money::naira(100), PIN, None)
+        .unwrap();
+    let chidi = wallet.open_account("Chidi", Bank::Kuda).unwrap();
+    assert!(![ada, bayo].contains(&chidi));
+    drop(wallet);
+
+    let wallet = Wallet::open(&path).unwrap();
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(700));
+    assert_eq!(wallet.accounts().len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures_carry_the_details_of_what_went_wrong() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let nobody = AccountNumber::new(Bank::Kuda, 999).unwrap();
+
+    match wallet.withdraw_from(ada, Money::naira(1_500), PIN, None) {
+        Err(WalletError::InsufficientFunds {
+            account,
+            needed,
+            available,
+        }) => {
+            assert_eq!(account, ada);
+            assert_eq!(needed, Money::naira(1_500));
+            assert_eq!(available, Money::naira(1_000));
+        }
+        other => panic!("expected insufficient funds, got {other:?}"),
+    }
+    let err = wallet
+        .deposit_to(nobody, Money::naira(1), None)
+        .unwrap_err();
+    assert!(matches!(err, WalletError::AccountNotFound(account) if account == nobody));
+    assert_eq!(err.to_string(), format!("account {nobody} not found"));
+    assert!(matches!(
+        wallet.transfer(ada, ada, Money::naira(1), PIN, None),
+        Err(WalletError::SameAccount(account)) if account == ada
+    ));
+}
+
+#[test]
+fn money_parses_what_it_displays() {
+    for (input, kobo) in [
+        ("5000", 500_000),
+        ("5000.5", 500_050),
+        ("5,000.05", 500_005),
+        ("₦1,234,567.89", 123_456_789),
+        ("0.01", 1),
+    ] {
+        let money: Money = input.parse().unwrap();
+        assert_eq!(money, Money::from_kobo(kobo), "{input}");
+        assert_eq!(money.to_string().parse::<Money>(), Ok(money));
+    }
+    assert_eq!(Money::from_kobo(123_456_789).to_string(), "₦1,234,567.89");
+    assert_eq!(Money::from_kobo(5).to_plain_string(), "0.05");
+}
+
+#[test]
+fn money_refuses_bad_and_oversized_amounts() {
+    assert_eq!("184467440737095516.15".parse::<Money>(), Ok(Money::MAX));
+    assert!(matches!(
+        "184467440737095516.16".parse::<Money>(),
+        Err(MoneyParseError::TooLarge(_))
+    ));
+    assert!(matches!(
+        "1.234".parse::<Money>(),
+        Err(MoneyParseError::TooPrecise(_))
+    ));
+    for input in ["", "-5", ".5", "1e3", "five"] {
+        assert!(
+            matches!(input.parse::<Money>(), Err(MoneyParseError::Invalid(_))),
+            "{input}"
+        );
+    }
+    assert_eq!(Money::MAX.checked_add(Money::from_kobo(1)), None);
+    assert_eq!(Money::ZERO.checked_sub(Money::from_kobo(1)), None);
+}
+
+#[test]
+fn zero_amounts_are_refused() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 0);
+    let receipt = wallet
+        .transfer(ada, bayo, Money::naira(100), PIN, None)
+        .unwrap();
+    let zero = Money::ZERO;
+
+    let results = [
+        wallet.deposit_to(ada, zero, None).err(),
+        wallet.withdraw_from(ada, zero, PIN, None).err(),
+        wallet.transfer(ada, bayo, zero, PIN, None).err(),
+        wallet
+            .authorize_hold(ada, zero, Duration::from_secs(60), PIN, None)
+            .err(),
+        wallet.refund(receipt.transaction_id, zero, None).err(),
+    ];
+    for result in results {
+        assert!(
+            matches!(result, Some(WalletError::ZeroAmount)),
+            "{result:?}"
+        );
+    }
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(900));
+}
+
+#[test]
+fn fee_file_configures_each_bank() {
+    let fees: FeeSchedules =
+        "# pricing\nKuda flat=10 percentage_bps=50 cap=50 free_per_month=2\n\nOpay flat=25.50\n"
+            .parse()
+            .unwrap();
+    let kuda = fees.for_bank(Bank::Kuda);
+    assert_eq!(kuda.free_transfers_left(0), 2);
+    assert_eq!(kuda.fee_for(Money::naira(1_000), 2), Some(Money::naira(15)));
+    assert_eq!(
+        kuda.fee_for(Money::naira(100_000), 2),
+        Some(Money::naira(50))
+    );
+    assert_eq!(
+        fees.for_bank(Bank::Opay).fee_for(Money::naira(1_000), 0),
+        Some("25.50".parse().unwrap())
+    );
+    assert_eq!(
+        fees.for_bank(Bank::PalmPay).fee_for(Money::naira(1_000), 0),
+        Some(Money::ZERO)
+    );
+}
+
+#[test]
+fn fee_file_errors_name_the_line() {
+    for (input, line) in [
+        ("Kuda flat=ten", 1),
+        ("# fees\nGTBank flat=10", 2),
+        ("Kuda flat=10\nKuda cap=20", 2),
+        ("Kuda surcharge=5", 1),
+        ("Kuda free_per_month", 1),
+    ] {
+        match input.parse::<FeeSchedules>() {
+            Err(fees::FeeError::FeeFile { line: found, .. }) => assert_eq!(found, line, "{input}"),
+            other => panic!("{input}: expected a fee file error, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn wallet_charges_fees_from_a_loaded_file() {
+    let dir = scratch_dir("load-fees");
+    let path = dir.join("fees.txt");
+    fs::write(&path, "Kuda flat=10\n").unwrap();
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.load_fees(&path).unwrap();
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Opay, 0);
+
+    let receipt = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap();
+    assert_eq!(receipt.fee, Money::naira(10));
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(3_990));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reversed_transfers_give_back_their_free_slot() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.fees = "Kuda flat=10 free_per_month=1".parse().unwrap();
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Opay, 0);
+
+    let sent = wallet
+        .transfer(uche, ada, Money::naira(500), PIN, None)
+        .unwrap();
+    assert_eq!(sent.fee, Money::ZERO);
+    let preview = wallet
+        .preview_transfer_fee(uche, ada, Money::naira(500))
+        .unwrap();
+    assert_eq!(
+        (preview.fee, preview.free_transfers_left),
+        (Money::naira(10), 0)
+    );
+
+    wallet.reverse(sent.transaction_id, None).unwrap();
+    let preview = wallet
+        .preview_transfer_fee(uche, ada, Money::naira(500))
+        .unwrap();
+    assert_eq!((preview.fee, preview.free_transfers_left), (Money::ZERO, 1));
+}
+
+/// What a `StubConnector` was asked to do, by reference.
+#[derive(Debug, Default)]
+struct Calls {
+    credited: Vec<String>,
+    debited: Vec<String>,
+    reversed: Vec<String>,
+}
+
+/// A connector that answers with scripted results and records each call.
+#[derive(Debug)]
+struct StubConnector {
+    bank: Bank,
+    name: String,
+    settle: Result<(), ConnectorError>,
+    requery: Result<SettlementStatus, ConnectorError>,
+    reverse: Result<(), ConnectorError>,
+    calls: Arc<Mutex<Calls>>,
+}
+
+impl StubConnector {
+    fn answering(bank: Bank, name: &str) -> Self {
+        Self {
+            bank,
+            name: name.to_string(),
+            settle: Ok(()),
+            requery: Ok(SettlementStatus::Successful),
+            reverse: Ok(()),
+            calls: Arc::default(),
+        }
+    }
+}
+
+impl BankConnector for StubConnector {
+    fn bank(&self) -> Bank {
+        self.bank
+    }
+
+    fn name_enquiry(&mut self, _account_number: AccountNumber) -> Result<String, ConnectorError> {
+        Ok(self.name.clone())
+    }
+
+    fn credit(
+        &mut self,
+        reference: &str,
+        _account_number: AccountNumber,
+        _amount: Money,
+    ) -> Result<(), ConnectorError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .credited
+            .push(reference.to_string());
+        self.settle.clone()
+    }
+
+    fn debit(
+        &mut self,
+        reference: &str,
+        _account_number: AccountNumber,
+        _amount: Money,
+    ) -> Result<(), ConnectorError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .debited
+            .push(reference.to_string());
+        self.settle.clone()
+    }
+
+    fn requery(&mut self, _reference: &str) -> Result<SettlementStatus, ConnectorError> {
+        self.requery.clone()
+    }
+
+    fn reverse(&mut self, reference: &str) -> Result<(), ConnectorError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .reversed
+            .push(reference.to_string());
+        self.reverse.clone()
+    }
+}
+
+/// Uche at Kuda and Ada at Opay, with Opay reached through `stub`.
+fn wallet_with_stub(stub: StubConnector) -> (Wallet, AccountNumber, AccountNumber) {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Opay, 0);
+    wallet.add_connector(Box::new(stub));
+    (wallet, uche, ada)
+}
+
+#[test]
+fn mock_switch_records_refused_credits_as_failed() {
+    let switch = MockSwitch::new(SwitchConfig {
+        failure_per_mille: 1_000,
+        ..SwitchConfig::default()
+    });
+    let account_number = AccountNumber::new(Bank::Opay, 1).unwrap();
+    switch.register_account(Bank::Opay, account_number, "Ada");
+    let mut connector = switch.connector(Bank::Opay);
+
+    assert!(matches!(
+        connector.credit("TRF-1", account_number, Money::naira(100)),
+        Err(ConnectorError::Rejected(..))
+    ));
+    assert_eq!(switch.status("TRF-1"), SettlementStatus::Failed);
+    assert!(connector.reverse("TRF-1").is_err());
+}
+
+#[test]
+fn refused_settlement_moves_no_money() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Opay, 0);
+    let switch = MockSwitch::new(SwitchConfig {
+        failure_per_mille: 1_000,
+        ..SwitchConfig::default()
+    });
+    switch.register_account(Bank::Opay, ada, "Ada");
+    wallet.add_connector(Box::new(switch.connector(Bank::Opay)));
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap_err();
+    assert!(matches!(err, WalletError::Settlement(_)), "{err:?}");
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+    assert_eq!(ledger_balance(&wallet, ada), Money::ZERO);
+}
+
+#[test]
+fn transfer_retried_after_a_switch_failure_goes_through() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Opay, 0);
+    let switch = MockSwitch::new(SwitchConfig {
+        failure_per_mille: 500,
+        ..SwitchConfig::default()
+    });
+    switch.register_account(Bank::Opay, ada, "Ada");
+    wallet.add_connector(Box::new(switch.connector(Bank::Opay)));
+
+    // Every retry lands in the same second, so only a fresh reference keeps
+    // the switch from refusing it as a duplicate of the failed attempt.
+    let mut failures = 0;
+    loop {
+        match wallet.transfer(uche, ada, Money::naira(1_000), PIN, None) {
+            Ok(_) => break,
+            Err(WalletError::Settlement(ConnectorError::Rejected(_, reason))) => {
+                assert!(!reason.starts_with("duplicate"), "{reason}");
+                failures += 1;
+                assert!(failures < 20, "transfer never went through");
+            }
+            Err(err) => panic!("{err:?}"),
+        }
+    }
+    assert!(failures > 0);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_000));
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+}
+
+#[test]
+fn timeout_that_requeries_as_failed_moves_no_money() {
+    let stub = StubConnector {
+        settle: Err(ConnectorError::Timeout(Bank::Opay)),
+        requery: Ok(SettlementStatus::Failed),
+        ..StubConnector::answering(Bank::Opay, "Ada")
+    };
+    let calls = Arc::clone(&stub.calls);
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap_err();
+    assert!(
+        matches!(err, WalletError::Settlement(ConnectorError::Rejected(..))),
+        "{err:?}"
+    );
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+    assert!(calls.lock().unwrap().reversed.is_empty());
+}
+
+#[test]
+fn unknown_settlement_is_reversed_at_the_bank() {
+    let stub = StubConnector {
+        settle: Err(ConnectorError::Timeout(Bank::Opay)),
+        requery: Err(ConnectorError::Timeout(Bank::Opay)),
+        ..StubConnector::answering(Bank::Opay, "Ada")
+    };
+    let calls = Arc::clone(&stub.calls);
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap_err();
+    assert!(
+        matches!(err, WalletError::Settlement(ConnectorError::Timeout(_))),
+        "{err:?}"
+    );
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.reversed, calls.credited);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+}
+
+#[test]
+fn settlement_that_cannot_be_reversed_is_reported_unknown() {
+    let stub = StubConnector {
+        settle: Err(ConnectorError::Timeout(Bank::Opay)),
+        requery: Err(ConnectorError::Timeout(Bank::Opay)),
+        reverse: Err(ConnectorError::Timeout(Bank::Opay)),
+        ..StubConnector::answering(Bank::Opay, "Ada")
+    };
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap_err();
+    assert!(matches!(err, WalletError::SettlementUnknown(_)), "{err:?}");
+}
+
+#[test]
+fn failed_commit_reverses_the_settled_credit() {
+    let stub = StubConnector::answering(Bank::Opay, "Ada");
+    let calls = Arc::clone(&stub.calls);
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+    let dir = scratch_dir("failed-commit");
+    let path = dir.join("wallet.log");
+    fs::write(&path, "").unwrap();
+    wallet.storage = Some(Storage::read_only(&path).unwrap());
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap_err();
+    assert!(matches!(err, WalletError::Storage(_)), "{err:?}");
+    {
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.credited.len(), 1);
+        assert_eq!(calls.reversed, calls.credited);
+    }
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+    assert_eq!(ledger_balance(&wallet, ada), Money::ZERO);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reversing_a_settled_transfer_reverses_it_at_the_bank() {
+    let stub = StubConnector::answering(Bank::Opay, "Ada");
+    let calls = Arc::clone(&stub.calls);
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let sent = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap();
+    wallet.reverse(sent.transaction_id, None).unwrap();
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.reversed, calls.credited);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+}
+
+#[test]
+fn reversal_refused_by_the_bank_changes_nothing() {
+    let stub = StubConnector {
+        reverse: Err(ConnectorError::Rejected(
+            Bank::Opay,
+            "already paid out".into(),
+        )),
+        ..StubConnector::answering(Bank::Opay, "Ada")
+    };
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let sent = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap();
+    assert!(wallet.reverse(sent.transaction_id, None).is_err());
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_000));
+    assert!(!wallet.adjustments.is_reversed(sent.transaction_id));
+}
+
+#[test]
+fn refunding_a_settled_transfer_debits_the_bank() {
+    let stub = StubConnector::answering(Bank::Opay, "Ada");
+    let calls = Arc::clone(&stub.calls);
+    let (mut wallet, uche, ada) = wallet_with_stub(stub);
+
+    let sent = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap();
+    wallet
+        .refund(sent.transaction_id, Money::naira(400), None)
+        .unwrap();
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.debited.len(), 1);
+    assert!(calls.debited[0].starts_with(&calls.credited[0]));
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_400));
+}
+
+/// Hammers one shared wallet with transfers from many threads and checks
+/// that no money was created or lost along the way.
+#[test]
+fn shared_wallet_conserves_money_under_concurrent_transfers() {
+    const ACCOUNTS: u64 = 10;
+    const THREADS: u64 = 8;
+    const TRANSFERS_PER_THREAD: u64 = 1_000;
+
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let accounts: Vec<_> = (0..ACCOUNTS)
+        .map(|index| {
+            let account_number =
+                funded(&mut wallet, &format!("Stress {index}"), Bank::Kuda, 10_000);
+            wallet.upgrade_tier(account_number, KycTier::Tier3).unwrap();
+            account_number
+        })
+        .collect();
+    let accounts = Arc::new(accounts);
+    let total_before = Money::naira(10_000 * ACCOUNTS);
+
+    let (handle, worker) = WalletHandle::spawn(wallet);
+    let clients: Vec<_> = (0..THREADS)
+        .map(|seed| {
+            let handle = handle.clone();
+            let accounts = Arc::clone(&accounts);
+            thread::spawn(move || {
+                let mut state = seed + 1;
+                let mut next = move || {
+                    state = state
+                        .wrapping_mul(6_364_136_223_846_793_005)
+                        .wrapping_add(1_442_695_040_888_963_407);
+                    state >> 33
+                };
+                for _ in 0..TRANSFERS_PER_THREAD {
+                    let from = accounts[(next() % ACCOUNTS) as usize];
+                    let to = accounts[(next() % ACCOUNTS) as usize];
+                    let amount = Money::naira(next() % 2_000 + 1);
+                    // Failures such as insufficient funds are expected; only
+                    // the totals matter here.
+                    let _ = handle.transfer(from, to, amount, PIN, None);
+                }
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().expect("stress client panicked");
+    }
+    drop(handle);
+    let wallet = worker.join().expect("wallet worker panicked");
+
+    let total_after = accounts
+        .iter()
+        .map(|&account_number| ledger_balance(&wallet, account_number))
+        .try_fold(Money::ZERO, Money::checked_add)
+        .unwrap();
+    assert_eq!(total_before, total_after, "money was not conserved");
+    assert!(wallet.trial_balance().is_balanced(), "books do not balance");
+}
+
+#[test]
+fn retried_key_returns_the_original_receipt() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+
+    let first = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, Some("t-1"))
+        .unwrap();
+    let retried = wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, Some("t-1"))
+        .unwrap();
+    assert_eq!(retried.transaction_id, first.transaction_id);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_000));
+}
+
+#[test]
+fn key_reused_for_another_request_is_a_conflict() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, Some("k"))
+        .unwrap();
+
+    let conflicts = [
+        wallet.transfer(uche, ada, Money::naira(2_000), PIN, Some("k")),
+        wallet.transfer(ada, uche, Money::naira(1_000), PIN, Some("k")),
+        wallet.withdraw_from(uche, Money::naira(1_000), PIN, Some("k")),
+        wallet.deposit_to(uche, Money::naira(1_000), Some("k")),
+    ];
+    for result in conflicts {
+        assert!(
+            matches!(result, Err(WalletError::IdempotencyConflict(ref key)) if key == "k"),
+            "{result:?}"
+        );
+    }
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_000));
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+}
+
+#[test]
+fn wallet_handle_passes_keys_through() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let (handle, worker) = WalletHandle::spawn(wallet);
+
+    let first = handle
+        .withdraw_from(uche, Money::naira(500), PIN, Some("w-1"))
+        .unwrap();
+    let retried = handle
+        .withdraw_from(uche, Money::naira(500), PIN, Some("w-1"))
+        .unwrap();
+    assert_eq!(retried.transaction_id, first.transaction_id);
+    assert!(matches!(
+        handle.deposit_to(uche, Money::naira(500), Some("w-1")),
+        Err(WalletError::IdempotencyConflict(_))
+    ));
+    drop(handle);
+    let wallet = worker.join().unwrap();
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_500));
+}
+
+#[test]
+fn key_is_written_on_the_record_it_guards() {
+    let dir = scratch_dir("keyed-record");
+    let path = dir.join("wallet.log");
+    let mut wallet = Wallet::open(&path).unwrap();
+    let uche = wallet.open_account("Uche", Bank::Kuda).unwrap();
+    let first = wallet
+        .deposit_to(uche, Money::naira(1_000), Some("d-1"))
+        .unwrap();
+    drop(wallet);
+    let log = fs::read_to_string(&path).unwrap();
+    let deposit = log.lines().find(|line| line.contains("Deposited")).unwrap();
+    assert!(deposit.contains(r#""key":"d-1""#), "{deposit}");
+
+    let mut wallet = Wallet::open(&path).unwrap();
+    let retried = wallet
+        .deposit_to(uche, Money::naira(1_000), Some("d-1"))
+        .unwrap();
+    assert_eq!(retried.transaction_id, first.transaction_id);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(1_000));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn available_balance(wallet: &Wallet, account_number: AccountNumber) -> Money {
+    wallet.balance_of(account_number).unwrap().available
+}
+
+#[test]
+fn voiding_a_hold_releases_it_without_a_debit() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let hold = wallet
+        .authorize_hold(
+            uche,
+            Money::naira(2_000),
+            Duration::from_secs(600),
+            PIN,
+            None,
+        )
+        .unwrap();
+    assert_eq!(available_balance(&wallet, uche), Money::naira(3_000));
+
+    wallet.void_hold(hold.id).unwrap();
+    assert_eq!(available_balance(&wallet, uche), Money::naira(5_000));
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(5_000));
+    assert!(matches!(
+        wallet.capture_hold(hold.id, Money::naira(1_000), None),
+        Err(WalletError::HoldNotActive(_))
+    ));
+    assert!(matches!(
+        wallet.void_hold(hold.id),
+        Err(WalletError::HoldNotActive(_))
+    ));
+}
+
+#[test]
+fn lapsed_and_unknown_holds_cannot_be_voided() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let hold = wallet
+        .authorize_hold(
+            uche,
+            Money::naira(2_000),
+            Duration::from_secs(600),
+            PIN,
+            None,
+        )
+        .unwrap();
+    clock.advance(Duration::from_secs(600));
+
+    assert!(matches!(
+        wallet.void_hold(hold.id),
+        Err(WalletError::HoldNotActive(_))
+    ));
+    assert!(matches!(
+        wallet.void_hold(hold.id + 1),
+        Err(WalletError::HoldNotFound(_))
+    ));
+    assert_eq!(available_balance(&wallet, uche), Money::naira(5_000));
+}
+
+#[test]
+fn retried_hold_and_capture_keys_act_once() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let place = |wallet: &mut Wallet| {
+        wallet
+            .authorize_hold(
+                uche,
+                Money::naira(2_000),
+                Duration::from_secs(600),
+                PIN,
+                Some("h-1"),
+            )
+            .unwrap()
+    };
+    let hold = place(&mut wallet);
+    assert_eq!(place(&mut wallet).id, hold.id);
+    assert_eq!(available_balance(&wallet, uche), Money::naira(3_000));
+
+    let captured = wallet
+        .capture_hold(hold.id, Money::naira(1_500), Some("c-1"))
+        .unwrap();
+    let retried = wallet
+        .capture_hold(hold.id, Money::naira(1_500), Some("c-1"))
+        .unwrap();
+    assert_eq!(retried.transaction_id, captured.transaction_id);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(3_500));
+}
+
+#[test]
+fn live_holds_count_against_the_daily_limit() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 50_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let hold = wallet
+        .authorize_hold(
+            uche,
+            Money::naira(40_000),
+            Duration::from_secs(600),
+            PIN,
+            None,
+        )
+        .unwrap();
+
+    let err = wallet
+        .transfer(uche, ada, Money::naira(20_000), PIN, None)
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            WalletError::LimitExceeded {
+                kind: LimitKind::Daily,
+                attempted,
+                ..
+            } if attempted == Money::naira(60_000)
+        ),
+        "{err:?}"
+    );
+    wallet
+        .capture_hold(hold.id, Money::naira(40_000), None)
+        .unwrap();
+    assert!(
+        wallet
+            .transfer(uche, ada, Money::naira(20_000), PIN, None)
+            .is_err()
+    );
+    wallet
+        .transfer(uche, ada, Money::naira(10_000), PIN, None)
+        .unwrap();
+}
+
+#[test]
+fn cli_places_captures_and_voids_holds() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let run = |wallet: &mut Wallet, line: &str| {
+        let words: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        cli::execute(wallet, &cli::Command::parse(&words).unwrap(), &mut pin).unwrap()
+    };
+
+    let placed = run(&mut wallet, &format!("hold {uche} 1500 10"));
+    assert!(placed.starts_with("Hold 1 of ₦1,500.00"), "{placed}");
+    run(&mut wallet, &format!("hold {uche} 1000 10"));
+    assert_eq!(available_balance(&wallet, uche), Money::naira(2_500));
+
+    run(&mut wallet, "capture 1 1200");
+    assert_eq!(run(&mut wallet, "void 2"), "Hold 2 voided\n");
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(3_800));
+    assert_eq!(available_balance(&wallet, uche), Money::naira(3_800));
+    assert!(cli::Command::parse(&["void".to_string(), "0".to_string()]).is_err());
+}
+
+#[test]
+fn retried_refund_and_reversal_keys_act_once() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let withdrawal = wallet
+        .withdraw_from(uche, Money::naira(1_000), PIN, None)
+        .unwrap();
+
+    let refunded = wallet
+        .refund(withdrawal.transaction_id, Money::naira(300), Some("r-1"))
+        .unwrap();
+    let retried = wallet
+        .refund(withdrawal.transaction_id, Money::naira(300), Some("r-1"))
+        .unwrap();
+    assert_eq!(retried.transaction_id, refunded.transaction_id);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_300));
+
+    let deposit = wallet.deposit_to(uche, Money::naira(500), None).unwrap();
+    let reversed = wallet.reverse(deposit.transaction_id, Some("v-1")).unwrap();
+    let retried = wallet.reverse(deposit.transaction_id, Some("v-1")).unwrap();
+    assert_eq!(retried.transaction_id, reversed.transaction_id);
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_300));
+}
+
+#[test]
+fn money_coming_back_is_held_to_the_maximum_balance() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 50_000);
+    let withdrawal = wallet
+        .withdraw_from(uche, Money::naira(10_000), PIN, None)
+        .unwrap();
+    for _ in 0..5 {
+        wallet.deposit_to(uche, Money::naira(50_000), None).unwrap();
+    }
+    wallet.deposit_to(uche, Money::naira(10_000), None).unwrap();
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(300_000));
+
+    for result in [
+        wallet.reverse(withdrawal.transaction_id, None),
+        wallet.refund(withdrawal.transaction_id, Money::naira(1), None),
+    ] {
+        assert!(matches!(
+            result,
+            Err(WalletError::LimitExceeded {
+                kind: LimitKind::MaxBalance,
+                ..
+            })
+        ));
+    }
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(300_000));
+}
+
+#[test]
+fn decided_disputes_close_the_transaction_to_adjustments() {
+    for outcome in [DisputeState::Won, DisputeState::Lost] {
+        let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+        let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+        let withdrawal = wallet
+            .withdraw_from(uche, Money::naira(1_000), PIN, None)
+            .unwrap();
+        let dispute = wallet.open_dispute(withdrawal.transaction_id).unwrap();
+        assert!(matches!(
+            wallet.open_dispute(withdrawal.transaction_id),
+            Err(WalletError::TransactionDisputed(_))
+        ));
+        wallet
+            .move_dispute(dispute.id, DisputeState::UnderReview)
+            .unwrap();
+        wallet.move_dispute(dispute.id, outcome).unwrap();
+        let balance = ledger_balance(&wallet, uche);
+
+        for result in [
+            wallet.open_dispute(withdrawal.transaction_id).map(|_| ()),
+            wallet.reverse(withdrawal.transaction_id, None).map(|_| ()),
+            wallet
+                .refund(withdrawal.transaction_id, Money::naira(100), None)
+                .map(|_| ()),
+        ] {
+            assert!(
+                matches!(
+                    result,
+                    Err(WalletError::DisputeDecided { dispute: id, state, .. })
+                        if id == dispute.id && state == outcome
+                ),
+                "{outcome:?}: {result:?}"
+            );
+        }
+        assert_eq!(ledger_balance(&wallet, uche), balance);
+    }
+}
+
+fn dollars(wallet: &Wallet, account_number: AccountNumber) -> Money {
+    wallet
+        .balance_in(account_number, Currency::Usd)
+        .unwrap()
+        .value
+}
+
+#[test]
+fn selling_naira_is_held_to_the_tier_limits() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.rates = Box::new("USD 1000".parse::<RateTable>().unwrap());
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 45_000);
+    wallet.deposit_to(uche, Money::naira(45_000), None).unwrap();
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+
+    let err = wallet
+        .convert(
+            uche,
+            Currency::Ngn,
+            Currency::Usd,
+            Money::naira(60_000),
+            PIN,
+            None,
+        )
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            WalletError::LimitExceeded {
+                kind: LimitKind::SingleTransaction,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+
+    wallet
+        .convert(
+            uche,
+            Currency::Ngn,
+            Currency::Usd,
+            Money::naira(30_000),
+            PIN,
+            None,
+        )
+        .unwrap();
+    let err = wallet
+        .transfer(uche, ada, Money::naira(25_000), PIN, None)
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            WalletError::LimitExceeded {
+                kind: LimitKind::Daily,
+                attempted,
+                ..
+            } if attempted == Money::naira(55_000)
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn buying_naira_does_not_count_as_sending() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.rates = Box::new("USD 1000".parse::<RateTable>().unwrap());
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 45_000);
+    wallet.deposit_to(uche, Money::naira(45_000), None).unwrap();
+
+    wallet
+        .convert(
+            uche,
+            Currency::Ngn,
+            Currency::Usd,
+            Money::naira(30_000),
+            PIN,
+            None,
+        )
+        .unwrap();
+    let usd = dollars(&wallet, uche);
+    wallet
+        .convert(uche, Currency::Usd, Currency::Ngn, usd, PIN, None)
+        .unwrap();
+    wallet
+        .withdraw_from(uche, Money::naira(20_000), PIN, None)
+        .unwrap();
+    assert!(matches!(
+        wallet.withdraw_from(uche, Money::naira(1), PIN, None),
+        Err(WalletError::LimitExceeded {
+            kind: LimitKind::Daily,
+            ..
+        })
+    ));
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[test]
+fn retried_conversion_and_currency_deposit_keys_act_once() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.rates = Box::new("USD 1000".parse::<RateTable>().unwrap());
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 0);
+    let remittance = Amount::new(Currency::Usd, Money::naira(50));
+
+    let deposited = wallet
+        .deposit_currency(uche, remittance, Some("fx-in"))
+        .unwrap();
+    let retried = wallet
+        .deposit_currency(uche, remittance, Some("fx-in"))
+        .unwrap();
+    assert_eq!((deposited, retried), (remittance, remittance));
+
+    let convert = |wallet: &mut Wallet| {
+        wallet
+            .convert(
+                uche,
+                Currency::Usd,
+                Currency::Ngn,
+                Money::naira(20),
+                PIN,
+                Some("fx-1"),
+            )
+            .unwrap()
+    };
+    let quote = convert(&mut wallet);
+    assert_eq!(convert(&mut wallet), quote);
+    assert_eq!(dollars(&wallet, uche), Money::naira(30));
+    assert_eq!(ledger_balance(&wallet, uche), quote.bought.value);
+}
+
+#[test]
+fn conversions_round_the_mid_half_to_even_and_the_customer_down() {
+    let rates: RateTable = "USD 1550.25\nspread_bps 100".parse().unwrap();
+    let quote = fx::quote(&rates, Currency::Usd, Currency::Ngn, Money::from_kobo(100)).unwrap();
+    assert_eq!(quote.mid.value, Money::from_kobo(155_025));
+    assert_eq!(quote.bought.value, Money::from_kobo(153_474));
+    assert_eq!(quote.spread.value, Money::from_kobo(1_551));
+
+    let rates: RateTable = "USD 1000".parse().unwrap();
+    let cents = |naira| {
+        let quote = fx::quote(&rates, Currency::Ngn, Currency::Usd, Money::naira(naira)).unwrap();
+        (
+            quote.mid.value.kobo(),
+            quote.bought.value.kobo(),
+            quote.spread.value.kobo(),
+        )
+    };
+    assert_eq!(cents(15), (2, 1, 1));
+    assert_eq!(cents(25), (2, 2, 0));
+    assert!(matches!(
+        fx::quote(&rates, Currency::Ngn, Currency::Usd, Money::naira(5)),
+        Err(FxError::TooSmall(_))
+    ));
+}
+
+#[test]
+fn spreads_are_bounded_by_the_whole_amount() {
+    assert!(matches!(
+        "USD 1000\nspread_bps 10001".parse::<RateTable>(),
+        Err(FxError::RateFile { line: 2, .. })
+    ));
+    let everything: RateTable = "USD 1000\nspread_bps 10000".parse().unwrap();
+    assert!(matches!(
+        fx::quote(&everything, Currency::Usd, Currency::Ngn, Money::naira(1)),
+        Err(FxError::TooSmall(_))
+    ));
+
+    #[derive(Debug)]
+    struct Greedy;
+    impl RateProvider for Greedy {
+        fn mid_rate(&self, _currency: Currency) -> Option<fx::Rate> {
+            Some(fx::Rate::ONE)
+        }
+        fn spread_bps(&self) -> u32 {
+            20_000
+        }
+    }
+    assert!(matches!(
+        fx::quote(&Greedy, Currency::Usd, Currency::Ngn, Money::naira(1)),
+        Err(FxError::InvalidSpread(20_000))
+    ));
+}
+
+#[test]
+fn wrong_pins_lock_the_account_until_a_reset_in_time() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let withdraw =
+        |wallet: &mut Wallet, pin: &str| wallet.withdraw_from(ada, Money::naira(100), pin, None);
+
+    for attempts_left in [2, 1] {
+        assert!(matches!(
+            withdraw(&mut wallet, "9999"),
+            Err(WalletError::IncorrectPin { attempts_left: left, .. }) if left == attempts_left
+        ));
+    }
+    assert!(matches!(
+        withdraw(&mut wallet, "9999"),
+        Err(WalletError::PinLocked(_))
+    ));
+    assert!(matches!(
+        withdraw(&mut wallet, PIN),
+        Err(WalletError::PinLocked(_))
+    ));
+
+    let lapsed = wallet.request_pin_reset(ada).unwrap();
+    clock.advance(Duration::from_secs(15 * 60 + 1));
+    assert!(matches!(
+        wallet.complete_pin_reset(ada, &lapsed, "5678"),
+        Err(WalletError::InvalidResetToken(_))
+    ));
+    let token = wallet.request_pin_reset(ada).unwrap();
+    assert!(matches!(
+        wallet.complete_pin_reset(ada, &token, "56a8"),
+        Err(WalletError::InvalidPinFormat)
+    ));
+    wallet.complete_pin_reset(ada, &token, "5678").unwrap();
+    assert!(matches!(
+        wallet.complete_pin_reset(ada, &token, "4321"),
+        Err(WalletError::InvalidResetToken(_))
+    ));
+
+    assert!(matches!(
+        withdraw(&mut wallet, PIN),
+        Err(WalletError::IncorrectPin { .. })
+    ));
+    withdraw(&mut wallet, "5678").unwrap();
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(900));
+}
+
+#[test]
+fn reactivated_accounts_can_be_debited_again() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.set_dormant_after(DAY * 30);
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    clock.advance(DAY * 31);
+
+    assert_eq!(wallet.mark_dormant_accounts().unwrap(), vec![uche]);
+    assert!(matches!(
+        wallet.withdraw_from(uche, Money::naira(100), PIN, None),
+        Err(WalletError::AccountNotActive {
+            state: AccountState::Dormant,
+            ..
+        })
+    ));
+    wallet
+        .reactivate(uche, "customer visited a branch")
+        .unwrap();
+    wallet
+        .withdraw_from(uche, Money::naira(100), PIN, None)
+        .unwrap();
+}
+
+#[test]
+fn reactivating_an_idle_account_records_it_dormant_first() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.set_dormant_after(DAY * 30);
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    clock.advance(DAY * 31);
+
+    wallet.reactivate(uche, "customer called").unwrap();
+    let states: Vec<_> = wallet
+        .state_history(uche)
+        .iter()
+        .map(|change| (change.from, change.to))
+        .collect();
+    assert_eq!(
+        states,
+        [
+            (AccountState::Active, AccountState::Dormant),
+            (AccountState::Dormant, AccountState::Active)
+        ]
+    );
+    wallet
+        .withdraw_from(uche, Money::naira(100), PIN, None)
+        .unwrap();
+}
+
+#[test]
+fn unfreezing_after_a_long_freeze_restores_debits() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.set_dormant_after(DAY * 30);
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    wallet.freeze(uche, "investigation").unwrap();
+    clock.advance(DAY * 60);
+
+    wallet.unfreeze(uche, "investigation closed").unwrap();
+    wallet
+        .withdraw_from(uche, Money::naira(100), PIN, None)
+        .unwrap();
+}
+
+#[test]
+fn closing_needs_a_sweep_account_and_no_holds() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+
+    assert!(matches!(
+        wallet.close_account(uche, None, "moving abroad"),
+        Err(WalletError::BalanceNotZero { balance, .. }) if balance == Money::naira(5_000)
+    ));
+    let hold = wallet
+        .authorize_hold(
+            uche,
+            Money::naira(1_000),
+            Duration::from_secs(600),
+            PIN,
+            None,
+        )
+        .unwrap();
+    assert!(matches!(
+        wallet.close_account(uche, Some(ada), "moving abroad"),
+        Err(WalletError::HoldsOutstanding(_))
+    ));
+    wallet.void_hold(hold.id).unwrap();
+
+    wallet
+        .close_account(uche, Some(ada), "moving abroad")
+        .unwrap();
+    assert_eq!(ledger_balance(&wallet, uche), Money::ZERO);
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(5_000));
+    assert_eq!(wallet.user(uche).unwrap().state, AccountState::Closed);
+    assert!(matches!(
+        wallet.deposit_to(uche, Money::naira(100), None),
+        Err(WalletError::AccountNotActive { .. })
+    ));
+    assert!(wallet.reactivate(uche, "changed their mind").is_err());
+}
+
+#[test]
+fn cli_changes_account_states() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let run = |wallet: &mut Wallet, words: &[&str]| {
+        let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        cli::execute(wallet, &cli::Command::parse(&words)?, &mut pin)
+    };
+    let uche_number = uche.to_string();
+    let ada_number = ada.to_string();
+
+    run(&mut wallet, &["freeze", &uche_number, "chargeback review"]).unwrap();
+    assert!(run(&mut wallet, &["reactivate", &uche_number, "done"]).is_err());
+    run(&mut wallet, &["unfreeze", &uche_number, "review closed"]).unwrap();
+    assert!(run(&mut wallet, &["close", &uche_number, " "]).is_err());
+    run(
+        &mut wallet,
+        &["close", &uche_number, "customer request", &ada_number],
+    )
+    .unwrap();
+
+    let states = run(&mut wallet, &["states", &uche_number]).unwrap();
+    let lines: Vec<_> = states.lines().collect();
+    assert_eq!(lines.len(), 3, "{states}");
+    assert!(lines[0].ends_with("Active -> Frozen  chargeback review"));
+    assert!(lines[2].ends_with("Active -> Closed  customer request"));
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(5_000));
+}
+
+#[test]
+fn cli_shows_the_latest_journal_entries() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    wallet
+        .transfer(uche, ada, Money::naira(1_200), PIN, None)
+        .unwrap();
+
+    let words: Vec<String> = ["journal", "1"].map(str::to_string).to_vec();
+    let out = cli::execute(&mut wallet, &cli::Command::parse(&words).unwrap(), &mut pin).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines.len(), 3, "{out}");
+    assert!(lines[0].starts_with("#2  2026-03-02  "), "{out}");
+    assert!(lines[1].contains(&format!("Customer {uche}")), "{out}");
+    assert!(lines[1].trim_end().ends_with("₦1,200.00"), "{out}");
+    assert!(lines[2].contains(&format!("Customer {ada}")), "{out}");
+}
+
+#[test]
+fn cli_asks_for_pins_instead_of_taking_them_as_arguments() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let words =
+        |line: &str| -> Vec<String> { line.split_whitespace().map(str::to_string).collect() };
+    assert!(matches!(
+        cli::Command::parse(&words(&format!("withdraw {uche} 100 {PIN}"))),
+        Err(cli::CliError::Usage(_))
+    ));
+
+    let withdraw = cli::Command::parse(&words(&format!("withdraw {uche} 100"))).unwrap();
+    let mut prompts = Vec::new();
+    let mut wrong = |prompt: &str| {
+        prompts.push(prompt.to_string());
+        Ok("9999".to_string())
+    };
+    assert!(matches!(
+        cli::execute(&mut wallet, &withdraw, &mut wrong),
+        Err(cli::CliError::Wallet(WalletError::IncorrectPin { .. }))
+    ));
+    assert_eq!(prompts, [format!("PIN for {uche}")]);
+    cli::execute(&mut wallet, &withdraw, &mut pin).unwrap();
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(4_900));
+}
+
+#[test]
+fn nuban_check_digit_catches_any_single_mistyped_digit() {
+    assert_eq!(
+        AccountNumber::new(Bank::Kuda, 1).unwrap().to_string(),
+        "0000000015"
+    );
+    let number = "1234567890";
+    assert_eq!(
+        AccountNumber::parse_for(number, Bank::Opay)
+            .unwrap()
+            .to_string(),
+        number
+    );
+    for position in 0..number.len() {
+        let mut typo = number.as_bytes().to_vec();
+        typo[position] = b'0' + (typo[position] - b'0' + 1) % 10;
+        let typo = String::from_utf8(typo).unwrap();
+        assert!(
+            matches!(
+                AccountNumber::parse_for(&typo, Bank::Opay),
+                Err(NubanError::CheckDigit(..))
+            ),
+            "{typo}"
+        );
+    }
+    for input in ["123456789", "12345678901", "12345 7890", "abcdefghij"] {
+        assert!(
+            matches!(input.parse::<AccountNumber>(), Err(NubanError::Format(_))),
+            "{input}"
+        );
+    }
+    assert!(AccountNumber::new(Bank::Opay, 1_000_000_000).is_none());
+}
+
+#[test]
+fn wallet_resolves_only_well_formed_numbers_at_their_own_bank() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = wallet.open_account("Ada", Bank::Kuda).unwrap();
+    assert!(ada.is_valid_for(Bank::Kuda));
+    let typed = ada.to_string();
+    assert_eq!(wallet.resolve_account(&typed, Bank::Kuda).unwrap(), ada);
+
+    let last = typed.as_bytes()[9] - b'0';
+    let typo = format!("{}{}", &typed[..9], (last + 1) % 10);
+    assert!(matches!(
+        wallet.resolve_account(&typo, Bank::Kuda),
+        Err(WalletError::InvalidAccountNumber(NubanError::CheckDigit(
+            ..
+        )))
+    ));
+}
+
+#[test]
+fn statement_totals_carry_the_opening_balance_to_the_closing_one() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 0);
+    clock.advance(DAY);
+    wallet
+        .transfer(ada, bayo, Money::naira(300), PIN, None)
+        .unwrap();
+    wallet.deposit_to(ada, Money::naira(200), None).unwrap();
+    clock.advance(DAY);
+    wallet
+        .withdraw_from(ada, Money::naira(100), PIN, None)
+        .unwrap();
+
+    let day = Date::new(2026, 3, 3);
+    let statement = wallet.statement(ada, day, day).unwrap();
+    assert_eq!(statement.opening_balance, Money::naira(1_000));
+    assert_eq!(statement.lines.len(), 2);
+    assert_eq!(statement.total_debits, Money::naira(300));
+    assert_eq!(statement.total_credits, Money::naira(200));
+    assert_eq!(statement.closing_balance, Money::naira(900));
+    let csv = statement.to_csv();
+    assert_eq!(
+        csv.lines().last(),
+        Some("2026-03-03,,,Closing balance,300.00,200.00,900.00")
+    );
+    assert_eq!(csv.lines().count(), 5);
+
+    let whole = wallet
+        .statement(ada, Date::new(2026, 3, 1), Date::new(2026, 3, 4))
+        .unwrap();
+    assert_eq!(whole.opening_balance, Money::ZERO);
+    assert_eq!(whole.closing_balance, ledger_balance(&wallet, ada));
+    assert!(matches!(
+        wallet.statement(ada, Date::new(2026, 3, 4), day),
+        Err(WalletError::InvalidDateRange { .. })
+    ));
+}
+
+#[test]
+fn end_of_day_runs_once_and_only_after_the_day_is_over() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    assert!(matches!(
+        wallet.run_end_of_day(Date::new(2026, 3, 2)),
+        Err(WalletError::EndOfDayInFuture(_))
+    ));
+    wallet.run_end_of_day(Date::new(2026, 3, 1)).unwrap();
+    assert!(matches!(
+        wallet.run_end_of_day(Date::new(2026, 3, 1)),
+        Err(WalletError::EndOfDayAlreadyRun(_))
+    ));
+}
+
+#[test]
+fn simple_interest_is_earned_on_the_principal_still_held() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 1, 1));
+    // 36.5% a year is 0.1% a day: ₦10 a day on ₦10,000.
+    let saver = wallet
+        .open_savings_account("Uche", Bank::Kuda, SavingsProduct::new(3_650))
+        .unwrap();
+    wallet.set_pin(saver, PIN).unwrap();
+    wallet
+        .deposit_to(saver, Money::naira(10_000), None)
+        .unwrap();
+
+    clock.advance(DAY * 31);
+    let paid = wallet.run_end_of_day(Date::new(2026, 1, 31)).unwrap();
+    assert_eq!(paid.len(), 1);
+    assert_eq!(paid[0].amount, Money::naira(310));
+
+    // Spending the interest with the rest leaves none of it in the account,
+    // so a fresh deposit is all principal.
+    wallet
+        .withdraw_from(saver, Money::naira(10_310), PIN, None)
+        .unwrap();
+    wallet
+        .deposit_to(saver, Money::naira(10_000), None)
+        .unwrap();
+    clock.advance(DAY);
+    wallet.run_end_of_day(Date::new(2026, 2, 1)).unwrap();
+    let savings = wallet.savings(saver).unwrap();
+    assert_eq!(savings.accrued_interest(), Money::naira(10));
+}
+
+#[test]
+fn monthly_orders_clamp_to_the_end_of_short_months() {
+    let on_31st = Frequency::Monthly(31);
+    assert_eq!(
+        on_31st.first_on_or_after(Date::new(2026, 2, 1)),
+        Date::new(2026, 2, 28)
+    );
+    assert_eq!(
+        on_31st.first_on_or_after(Date::new(2028, 2, 1)),
+        Date::new(2028, 2, 29)
+    );
+    assert_eq!(
+        on_31st.first_on_or_after(Date::new(2026, 4, 1)),
+        Date::new(2026, 4, 30)
+    );
+    assert_eq!(
+        Frequency::Weekly(Weekday::Friday).first_on_or_after(Date::new(2026, 3, 4)),
+        Date::new(2026, 3, 6)
+    );
+    assert_eq!(
+        Frequency::Weekly(Weekday::Friday).first_on_or_after(Date::new(2026, 3, 6)),
+        Date::new(2026, 3, 6)
+    );
+}
+
+/// Runs the scheduler once a day for `days` days and returns what each run
+/// did, as (run date, due date, outcome).
+fn run_daily(wallet: &mut Wallet, clock: &ManualClock, days: u32) -> Vec<(Date, Date, RunOutcome)> {
+    let mut runs = Vec::new();
+    for _ in 0..days {
+        let today = Date::from_timestamp(wallet.now());
+        for run in wallet.run_standing_orders().unwrap() {
+            runs.push((today, run.due, run.outcome));
+        }
+        clock.advance(DAY);
+    }
+    runs
+}
+
+#[test]
+fn retried_standing_order_key_sets_up_one_order() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let tenant = funded(&mut wallet, "Tenant", Bank::Kuda, 1_000);
+    let landlord = funded(&mut wallet, "Landlord", Bank::Kuda, 0);
+    let add = |wallet: &mut Wallet| {
+        wallet
+            .add_standing_order(
+                tenant,
+                landlord,
+                Money::naira(1_000),
+                Frequency::Monthly(1),
+                PIN,
+                Some("so-1"),
+            )
+            .unwrap()
+    };
+    let order = add(&mut wallet);
+    assert_eq!(add(&mut wallet).id, order.id);
+    assert_eq!(wallet.standing_orders_for(tenant).len(), 1);
+}
+
+#[test]
+fn monthly_order_on_the_31st_pays_on_each_month_end() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 1, 30));
+    let tenant = funded(&mut wallet, "Tenant", Bank::Kuda, 10_000);
+    let landlord = funded(&mut wallet, "Landlord", Bank::Kuda, 0);
+    let order = wallet
+        .add_standing_order(
+            tenant,
+            landlord,
+            Money::naira(1_000),
+            Frequency::Monthly(31),
+            PIN,
+            None,
+        )
+        .unwrap();
+    assert_eq!(order.next_due, Date::new(2026, 1, 31));
+
+    let paid: Vec<_> = run_daily(&mut wallet, &clock, 70)
+        .into_iter()
+        .map(|(today, due, outcome)| {
+            assert!(matches!(outcome, RunOutcome::Paid(_)), "{outcome:?}");
+            assert_eq!(today, due);
+            due
+        })
+        .collect();
+    assert_eq!(
+        paid,
+        [
+            Date::new(2026, 1, 31),
+            Date::new(2026, 2, 28),
+            Date::new(2026, 3, 31)
+        ]
+    );
+    assert_eq!(ledger_balance(&wallet, landlord), Money::naira(3_000));
+}
+
+#[test]
+fn unfunded_payment_retries_until_the_window_closes_then_is_missed() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    wallet.set_standing_order_retry_window(DAY * 2);
+    let tenant = funded(&mut wallet, "Tenant", Bank::Kuda, 500);
+    let landlord = funded(&mut wallet, "Landlord", Bank::Kuda, 0);
+    let order = wallet
+        .add_standing_order(
+            tenant,
+            landlord,
+            Money::naira(1_000),
+            Frequency::Weekly(Weekday::Friday),
+            PIN,
+            None,
+        )
+        .unwrap();
+
+    let runs = run_daily(&mut wallet, &clock, 7);
+    let summary: Vec<_> = runs
+        .iter()
+        .map(|(today, due, outcome)| {
+            let outcome = match outcome {
+                RunOutcome::Paid(_) => "paid",
+                RunOutcome::Retrying { until, .. } => {
+                    assert_eq!(Date::from_timestamp(*until), Date::new(2026, 3, 8));
+                    "retrying"
+                }
+                RunOutcome::Missed(_) => "missed",
+            };
+            (today.day, due.day, outcome)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [(6, 6, "retrying"), (7, 6, "retrying"), (8, 6, "missed")]
+    );
+    assert_eq!(
+        wallet.standing_order(order.id).unwrap().next_due,
+        Date::new(2026, 3, 13)
+    );
+    assert_eq!(ledger_balance(&wallet, landlord), Money::ZERO);
+}
+
+#[test]
+fn payment_funded_within_the_retry_window_goes_through() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 6));
+    let tenant = funded(&mut wallet, "Tenant", Bank::Kuda, 500);
+    let landlord = funded(&mut wallet, "Landlord", Bank::Kuda, 0);
+    wallet
+        .add_standing_order(
+            tenant,
+            landlord,
+            Money::naira(1_000),
+            Frequency::Weekly(Weekday::Friday),
+            PIN,
+            None,
+        )
+        .unwrap();
+
+    assert!(matches!(
+        run_daily(&mut wallet, &clock, 1)[..],
+        [(_, _, RunOutcome::Retrying { .. })]
+    ));
+    wallet
+        .deposit_to(tenant, Money::naira(1_000), None)
+        .unwrap();
+    assert!(matches!(
+        run_daily(&mut wallet, &clock, 1)[..],
+        [(_, due, RunOutcome::Paid(_))] if due == Date::new(2026, 3, 6)
+    ));
+    assert_eq!(ledger_balance(&wallet, landlord), Money::naira(1_000));
+}
+
+#[test]
+fn balance_on_replays_history_up_to_the_close_of_that_day() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    let bayo = funded(&mut wallet, "Bayo", Bank::Kuda, 0);
+    clock.advance(DAY);
+    wallet
+        .transfer(ada, bayo, Money::naira(400), PIN, None)
+        .unwrap();
+    clock.advance(DAY);
+    wallet
+        .withdraw_from(ada, Money::naira(100), PIN, None)
+        .unwrap();
+
+    let on = |date| wallet.balance_on(ada, date).map(|balance| balance.ledger);
+    assert_eq!(on(Date::new(2026, 3, 1)), None);
+    assert_eq!(on(Date::new(2026, 3, 2)), Some(Money::naira(1_000)));
+    assert_eq!(on(Date::new(2026, 3, 3)), Some(Money::naira(600)));
+    assert_eq!(on(Date::new(2026, 3, 4)), Some(Money::naira(500)));
+    assert_eq!(
+        wallet
+            .balance_on(bayo, Date::new(2026, 3, 2))
+            .unwrap()
+            .ledger,
+        Money::ZERO
+    );
+}
+
+#[test]
+fn records_dated_before_the_last_one_are_refused() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 1_000);
+    wallet.set_clock(Arc::new(ManualClock::new(clock.now() - 60)));
+
+    assert!(matches!(
+        wallet.deposit_to(ada, Money::naira(100), None),
+        Err(WalletError::OutOfOrder { .. })
+    ));
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+}
+
+/// A wallet log at `path` holding one account with 1,000 deposited, and its
+/// audit log.
+fn audited_log(path: &Path) -> AccountNumber {
+    let mut wallet = Wallet::open(path).unwrap();
+    let uche = wallet.open_account("Uche", Bank::Kuda).unwrap();
+    wallet.deposit_to(uche, Money::naira(1_000), None).unwrap();
+    uche
+}
+
+fn deposit_line(path: &Path) -> String {
+    let log = fs::read_to_string(path).unwrap();
+    log.lines()
+        .find(|line| line.contains(r#""Deposited""#))
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn unaudited_last_record_is_set_aside_not_replayed() {
+    let dir = scratch_dir("unaudited-record");
+    let path = dir.join("wallet.log");
+    let uche = audited_log(&path);
+    let deposit = deposit_line(&path);
+    let log = fs::read_to_string(&path).unwrap();
+    fs::write(&path, format!("{log}{deposit}\n")).unwrap();
+
+    let wallet = Wallet::open(&path).unwrap();
+    assert!(matches!(wallet.unaudited(), Some(Record::Deposited { .. })));
+    assert_eq!(ledger_balance(&wallet, uche), Money::naira(1_000));
+    assert_eq!(fs::read_to_string(&path).unwrap(), log);
+    assert_eq!(
+        fs::read_to_string(AuditLog::unaudited_path_for(&path)).unwrap(),
+        format!("{deposit}\n")
+    );
+    assert_eq!(wallet.verify_audit(None).unwrap().entries, 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn more_than_one_unaudited_record_is_refused() {
+    let dir = scratch_dir("unaudited-records");
+    let path = dir.join("wallet.log");
+    audited_log(&path);
+    let deposit = deposit_line(&path);
+    let log = fs::read_to_string(&path).unwrap();
+    fs::write(&path, format!("{log}{deposit}\n{deposit}\n")).unwrap();
+
+    let err = Wallet::open(&path).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            WalletError::Audit(AuditError::LengthMismatch {
+                records: 4,
+                entries: 2
+            })
+        ),
+        "{err:?}"
+    );
+    assert!(!AuditLog::unaudited_path_for(&path).exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn edited_record_is_refused_on_open() {
+    let dir = scratch_dir("edited-record");
+    let path = dir.join("wallet.log");
+    audited_log(&path);
+    let log = fs::read_to_string(&path).unwrap();
+    fs::write(&path, log.replace("100000", "900000")).unwrap();
+
+    let err = Wallet::open(&path).unwrap_err();
+    assert!(
+        matches!(err, WalletError::Audit(AuditError::RecordMismatch(2))),
+        "{err:?}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn audit_checks_a_published_head() {
+    let dir = scratch_dir("published-head");
+    let path = dir.join("wallet.log");
+    let mut wallet = Wallet::open(&path).unwrap();
+    let uche = wallet.open_account("Uche", Bank::Kuda).unwrap();
+    let published = wallet.audit_head().to_string();
+    wallet.deposit_to(uche, Money::naira(1_000), None).unwrap();
+
+    assert_eq!(wallet.verify_audit(Some(&published)).unwrap().entries, 2);
+    let err = wallet.verify_audit(Some(&"f".repeat(64))).unwrap_err();
+    assert!(
+        matches!(err, WalletError::Audit(AuditError::UnknownHead(_))),
+        "{err:?}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Serves `wallet` on an ephemeral port for the rest of the test run.
+fn serve(wallet: Wallet) -> SocketAddr {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let address = server.server_addr().to_ip().unwrap();
+    let (handle, _worker) = WalletHandle::spawn(wallet);
+    thread::spawn(move || api::serve_on(server, handle));
+    address
+}
+
+/// Sends one request and returns its status and JSON body.
+fn request(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut head = format!(
+        "{method} {path} HTTP/1.1\r\nHost: wallet\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).unwrap();
+    // A server that turns the body away may stop reading it part way.
+    let _ = stream.write_all(body.as_bytes());
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    let response = String::from_utf8(response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1;
+    let body = if body.is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_str(body).unwrap()
+    };
+    (status, body)
+}
+
+const JSON: (&str, &str) = ("Content-Type", "application/json");
+
+#[test]
+fn api_maps_errors_to_statuses() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 1_000).to_string();
+    let address = serve(wallet);
+    let code = |(status, body): (u16, serde_json::Value)| {
+        (status, body["error"]["code"].as_str().unwrap().to_string())
+    };
+
+    let (status, body) = request(
+        address,
+        "POST",
+        "/accounts",
+        &[JSON],
+        r#"{"name":"Ada","bank":"Opay"}"#,
+    );
+    assert_eq!(status, 201);
+    assert_eq!(body["name"], "Ada");
+    let (status, body) = request(address, "GET", &format!("/accounts/{uche}"), &[], "");
+    assert_eq!(status, 200);
+    assert_eq!(body["balance"]["ledger"], 100_000);
+
+    let cases = [
+        (
+            "GET",
+            "/nowhere".to_string(),
+            vec![],
+            String::new(),
+            404,
+            "not_found",
+        ),
+        (
+            "GET",
+            "/accounts/0000000019".to_string(),
+            vec![],
+            String::new(),
+            404,
+            "account_not_found",
+        ),
+        (
+            "GET",
+            "/accounts/123".to_string(),
+            vec![],
+            String::new(),
+            400,
+            "invalid_account_number",
+        ),
+        (
+            "DELETE",
+            "/accounts".to_string(),
+            vec![],
+            String::new(),
+            405,
+            "method_not_allowed",
+        ),
+        (
+            "POST",
+            format!("/accounts/{uche}/deposits"),
+            vec![("Content-Type", "text/plain")],
+            r#"{"amount":100}"#.to_string(),
+            415,
+            "unsupported_media_type",
+        ),
+        (
+            "POST",
+            format!("/accounts/{uche}/deposits"),
+            vec![JSON],
+            format!(r#"{{"amount":100,"pad":"{}"}}"#, "x".repeat(70 * 1024)),
+            413,
+            "payload_too_large",
+        ),
+        (
+            "POST",
+            format!("/accounts/{uche}/deposits"),
+            vec![JSON],
+            r#"{"amount":"lots"}"#.to_string(),
+            400,
+            "bad_request",
+        ),
+        (
+            "POST",
+            format!("/accounts/{uche}/withdrawals"),
+            vec![JSON],
+            format!(r#"{{"amount":500000,"pin":"{PIN}"}}"#),
+            422,
+            "insufficient_funds",
+        ),
+        (
+            "POST",
+            format!("/accounts/{uche}/withdrawals"),
+            vec![JSON],
+            r#"{"amount":100,"pin":"9999"}"#.to_string(),
+            403,
+            "incorrect_pin",
+        ),
+    ];
+    for (method, path, headers, body, status, error) in cases {
+        assert_eq!(
+            code(request(address, method, &path, &headers, &body)),
+            (status, error.to_string()),
+            "{method} {path}"
+        );
+    }
+}
+
+#[test]
+fn api_replays_idempotent_requests_and_rejects_reused_keys() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 0);
+    let address = serve(wallet);
+    let path = format!("/accounts/{uche}/deposits");
+    let key = ("Idempotency-Key", "deposit-1");
+
+    let (status, first) = request(address, "POST", &path, &[JSON, key], r#"{"amount":5000}"#);
+    assert_eq!(status, 201);
+    let (status, retried) = request(address, "POST", &path, &[JSON, key], r#"{"amount":5000}"#);
+    assert_eq!(status, 201);
+    assert_eq!(retried["transaction_id"], first["transaction_id"]);
+
+    let (status, body) = request(address, "POST", &path, &[JSON, key], r#"{"amount":9000}"#);
+    assert_eq!(status, 422);
+    assert_eq!(body["error"]["code"], "idempotency_key_reused");
+    let (status, _) = request(
+        address,
+        "POST",
+        &path,
+        &[JSON, ("Idempotency-Key", " ")],
+        r#"{"amount":5000}"#,
+    );
+    assert_eq!(status, 400);
+
+    let (_, balance) = request(
+        address,
+        "GET",
+        &format!("/accounts/{uche}/balance"),
+        &[],
+        "",
+    );
+    assert_eq!(balance["ledger"], 5_000);
+}
+
+#[test]
+fn api_filters_transactions_by_kind() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 5_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    wallet
+        .withdraw_from(uche, Money::naira(500), PIN, None)
+        .unwrap();
+    wallet
+        .transfer(uche, ada, Money::naira(1_000), PIN, None)
+        .unwrap();
+    let address = serve(wallet);
+    let total = |query: &str| {
+        let (status, body) = request(
+            address,
+            "GET",
+            &format!("/accounts/{uche}/transactions?{query}"),
+            &[],
+            "",
+        );
+        (status, body["total"].clone())
+    };
+
+    assert_eq!(total(""), (200, 3.into()));
+    assert_eq!(total("kind=Withdrawal"), (200, 1.into()));
+    assert_eq!(total("kind=deposit,TransferOut"), (200, 2.into()));
+    assert_eq!(total("kind=deposit%2CTransferOut"), (200, 2.into()));
+    assert_eq!(total("kind=Deposit%2").0, 400);
+    assert_eq!(total("kind=Deposit&kind=Withdrawal"), (200, 2.into()));
+    assert_eq!(total("kind=TransferIn"), (200, 0.into()));
+    assert_eq!(total("kind=Gift").0, 400);
+}
+
+fn payroll(funding: AccountNumber, rows: &[(AccountNumber, &str, &str)]) -> PayoutBatch {
+    let mut csv = "account_number,amount,narration\n".to_string();
+    for (account_number, amount, narration) in rows {
+        csv.push_str(&format!("{account_number},{amount},{narration}\n"));
+    }
+    PayoutBatch::parse(Some("payroll-2026-03"), funding, &csv).unwrap()
+}
+
+fn paid_ids(report: &PayoutReport) -> Vec<u64> {
+    report
+        .results
+        .iter()
+        .map(|result| match &result.outcome {
+            PayoutOutcome::Paid(receipt) => receipt.transaction_id,
+            PayoutOutcome::Failed(err) => panic!("line {} failed: {err}", result.row.line),
+        })
+        .collect()
+}
+
+#[test]
+fn paid_payout_rows_stay_paid_after_idempotency_keys_expire() {
+    let (mut wallet, clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 20_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let bola = funded(&mut wallet, "Bola", Bank::Kuda, 0);
+    let batch = payroll(uche, &[(ada, "1000", "Salary"), (bola, "2000", "Salary")]);
+
+    let first = paid_ids(&wallet.run_payouts(&batch, PIN).unwrap());
+    clock.advance(3 * DAY);
+    let again = wallet.run_payouts(&batch, PIN).unwrap();
+    assert_eq!(paid_ids(&again), first);
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+    assert_eq!(ledger_balance(&wallet, bola), Money::naira(2_000));
+    assert_eq!(wallet.validate_payouts(&batch).unwrap().rows, 0);
+}
+
+#[test]
+fn payout_row_edited_after_it_was_paid_is_refused() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 20_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let bola = funded(&mut wallet, "Bola", Bank::Kuda, 0);
+    let batch = payroll(uche, &[(ada, "1000", "Salary"), (bola, "2000", "Salary")]);
+    wallet.run_payouts(&batch, PIN).unwrap();
+
+    for edited in [
+        payroll(uche, &[(ada, "1000", "Salary"), (bola, "2500", "Salary")]),
+        payroll(uche, &[(ada, "1000", "Salary"), (ada, "2000", "Salary")]),
+        payroll(uche, &[(ada, "1000", "Salary"), (bola, "2000", "Bonus")]),
+    ] {
+        let err = wallet.run_payouts(&edited, PIN).unwrap_err();
+        let WalletError::Payout(PayoutError::InvalidRows { rows, .. }) = err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].line, 3);
+    }
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+    assert_eq!(ledger_balance(&wallet, bola), Money::naira(2_000));
+}
+
+#[test]
+fn paid_payout_rows_survive_reopening_the_log() {
+    let dir = scratch_dir("paid-rows");
+    let path = dir.join("wallet.log");
+    let mut wallet = Wallet::open(&path).unwrap();
+    wallet.pin_policy = PinPolicy {
+        rounds: 1,
+        ..PinPolicy::default()
+    };
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 20_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let batch = payroll(uche, &[(ada, "1000", "Salary")]);
+    let first = paid_ids(&wallet.run_payouts(&batch, PIN).unwrap());
+    drop(wallet);
+
+    let mut wallet = Wallet::open(&path).unwrap();
+    assert_eq!(paid_ids(&wallet.run_payouts(&batch, PIN).unwrap()), first);
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(1_000));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn same_batch_id_from_another_funding_account_is_paid_separately() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 20_000);
+    let bola = funded(&mut wallet, "Bola", Bank::Kuda, 20_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+
+    wallet
+        .run_payouts(&payroll(uche, &[(ada, "1000", "Salary")]), PIN)
+        .unwrap();
+    let report = wallet
+        .run_payouts(&payroll(bola, &[(ada, "1000", "Salary")]), PIN)
+        .unwrap();
+    assert_eq!(report.summary().paid, 1);
+    assert_eq!(ledger_balance(&wallet, ada), Money::naira(2_000));
+    assert_eq!(ledger_balance(&wallet, bola), Money::naira(19_000));
+}
+
+#[test]
+fn payout_files_must_name_their_batch() {
+    let (mut wallet, _clock) = wallet_on(Date::new(2026, 3, 2));
+    let uche = funded(&mut wallet, "Uche", Bank::Kuda, 20_000);
+    let ada = funded(&mut wallet, "Ada", Bank::Kuda, 0);
+    let dir = scratch_dir("payout-batch-id");
+    let unnamed = dir.join("payroll.csv");
+    fs::write(&unnamed, format!("{ada},1000,Salary\n")).unwrap();
+    let named = dir.join("named.csv");
+    fs::write(&named, format!("# batch: march\n{ada},1000,Salary\n")).unwrap();
+    let check = |wallet: &mut Wallet, file: &Path, rest: &[&str]| {
+        let mut words = vec![
+            "payout-check".to_string(),
+            uche.to_string(),
+            file.display().to_string(),
+        ];
+        words.extend(rest.iter().map(|word| word.to_string()));
+        cli::execute(wallet, &cli::Command::parse(&words).unwrap(), &mut pin)
+    };
+
+    assert!(matches!(
+        check(&mut wallet, &unnamed, &[]),
+        Err(cli::CliError::Wallet(WalletError::Payout(
+            PayoutError::NoBatchId
+        )))
+    ));
+    let checked = check(&mut wallet, &unnamed, &["--batch", "march"]).unwrap();
+    assert!(checked.starts_with("march: 1 rows"), "{checked}");
+    let checked = check(&mut wallet, &named, &[]).unwrap();
+    assert!(checked.starts_with("march: 1 rows"), "{checked}");
+    assert!(matches!(
+        check(&mut wallet, &named, &["--batch", "april"]),
+        Err(cli::CliError::Wallet(WalletError::Payout(
+            PayoutError::BatchMismatch { .. }
+        )))
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
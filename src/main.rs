@@ -23,6 +23,16 @@ enum Option{
 
 
 fn main() {
+    // `cargo run -- wallet [COMMAND]` runs the wallet tool instead of the
+    // lessons; `cargo run -- wallet help` lists its commands.
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("wallet") {
+        if let Err(err) = Assignments::wallet::run(&args[2..]) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
     file_system();
     error();
     let sample = Option::None;